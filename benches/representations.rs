@@ -0,0 +1,214 @@
+//! compare the four config backends at 2, 100 and 10,000 provider entries,
+//! `cargo bench` then `cargo run --bin bench_report` renders the numbers as markdown
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_trait_object_demo::provider::ProviderAccess;
+use serde_trait_object_demo::{config_enum, config_struct, config_struct_boxed_trait, config_trait_object};
+
+const SIZES: &[usize] = &[2, 100, 10_000];
+
+/// every other entry is a Claude2, the active provider `provider-0` is an OpenaiMobile
+fn name(i: usize) -> String {
+    format!("provider-{}", i)
+}
+
+fn cookies() -> HashMap<String, String> {
+    HashMap::from([("session".to_string(), "cookie-value".to_string())])
+}
+
+fn trait_object_config(n: usize) -> config_trait_object::Config {
+    let mut cfg = config_trait_object::Config {
+        provider: Some(name(0).into()),
+        provider_config: config_trait_object::ProviderConfigMap::new(),
+        ..Default::default()
+    };
+    for i in 0..n {
+        let pcfg: Box<dyn config_trait_object::ProviderConfig> = if i % 2 == 0 {
+            Box::new(config_trait_object::OpenaiMobile { token: Some(format!("tk-{}", i)), cookies: cookies(), ..Default::default() })
+        } else {
+            Box::new(config_trait_object::Claude2 { cookies: cookies(), ..Default::default() })
+        };
+        cfg.provider_config.set(&name(i), pcfg);
+    }
+    cfg
+}
+
+fn enum_config(n: usize) -> config_enum::Config {
+    let mut cfg = config_enum::Config {
+        provider: Some(name(0)),
+        provider_config: config_enum::ProviderConfigMap::new(),
+    };
+    for i in 0..n {
+        let pcfg = if i % 2 == 0 {
+            config_enum::ProviderConfig::OpenaiMobile(config_enum::OpenaiMobile { token: Some(format!("tk-{}", i)), cookies: cookies(), ..Default::default() })
+        } else {
+            config_enum::ProviderConfig::Claude2(config_enum::Claude2 { cookies: cookies(), ..Default::default() })
+        };
+        cfg.provider_config.set(&name(i), pcfg);
+    }
+    cfg
+}
+
+fn struct_config(n: usize) -> config_struct::Config {
+    let mut cfg = config_struct::Config { provider: Some(name(0)), ..Default::default() };
+    for i in 0..n {
+        let pcfg = if i % 2 == 0 {
+            config_struct::ProviderConfig {
+                kind: Some("openai_mobile".to_string()),
+                api_base: Some("https://api.openai.com".to_string()),
+                token: Some(format!("tk-{}", i)),
+                model: Some("davinci".to_string()),
+                cookies: cookies(),
+            }
+        } else {
+            config_struct::ProviderConfig {
+                kind: Some("claude2".to_string()),
+                api_base: Some("https://claude.io".to_string()),
+                cookies: cookies(),
+                ..Default::default()
+            }
+        };
+        cfg.provider_config.insert(name(i), pcfg);
+    }
+    cfg
+}
+
+fn boxed_trait_config(n: usize) -> config_struct_boxed_trait::Config {
+    let mut cfg = config_struct_boxed_trait::Config { provider: Some(name(0)), ..Default::default() };
+    cfg.provider_config.openai_mobile.clear();
+    cfg.provider_config.claude2.clear();
+    for i in 0..n {
+        if i % 2 == 0 {
+            cfg.provider_config.openai_mobile.insert(name(i), config_struct_boxed_trait::OpenaiMobile { token: Some(format!("tk-{}", i)), cookies: cookies(), ..Default::default() });
+        } else {
+            cfg.provider_config.claude2.insert(name(i), config_struct_boxed_trait::Claude2 { cookies: cookies(), ..Default::default() });
+        }
+    }
+    cfg
+}
+
+/// the same code path for `Box<dyn ProviderConfig>` and the enum
+fn read_provider<P: ProviderAccess + ?Sized>(pcfg: &P) -> usize {
+    pcfg.api_base().len() + pcfg.token().len() + pcfg.model().map_or(0, |model| model.len())
+}
+
+/// deserialize/serialize in YAML and JSON, clone, lookup and mutation of one backend,
+/// `build` finishes what deserializing left for later, so every backend is timed building all its provider configs
+macro_rules! bench_backend {
+    ($c:expr, $backend:literal, $config:ident, $ty:ty, build: $build:expr, lookup: $lookup:expr, mutate: $mutate:expr) => {
+        for &n in SIZES {
+            let cfg = $config(n);
+            let yaml = serde_yaml::to_string(&cfg).unwrap();
+            let json = serde_json::to_string(&cfg).unwrap();
+
+            $c.benchmark_group("deserialize_yaml")
+                .bench_with_input(BenchmarkId::new($backend, n), &yaml, |b, yaml| {
+                    b.iter(|| $build(serde_yaml::from_str::<$ty>(black_box(yaml)).unwrap()))
+                });
+            $c.benchmark_group("serialize_yaml")
+                .bench_with_input(BenchmarkId::new($backend, n), &cfg, |b, cfg| {
+                    b.iter(|| serde_yaml::to_string(black_box(cfg)).unwrap())
+                });
+            $c.benchmark_group("deserialize_json")
+                .bench_with_input(BenchmarkId::new($backend, n), &json, |b, json| {
+                    b.iter(|| $build(serde_json::from_str::<$ty>(black_box(json)).unwrap()))
+                });
+            $c.benchmark_group("serialize_json")
+                .bench_with_input(BenchmarkId::new($backend, n), &cfg, |b, cfg| {
+                    b.iter(|| serde_json::to_string(black_box(cfg)).unwrap())
+                });
+            $c.benchmark_group("clone")
+                .bench_with_input(BenchmarkId::new($backend, n), &cfg, |b, cfg| {
+                    b.iter(|| black_box(cfg).clone())
+                });
+            $c.benchmark_group("lookup")
+                .bench_with_input(BenchmarkId::new($backend, n), &cfg, |b, cfg| {
+                    b.iter(|| $lookup(black_box(cfg)))
+                });
+            let mut cfg = cfg;
+            $c.benchmark_group("mutate")
+                .bench_function(BenchmarkId::new($backend, n), |b| {
+                    b.iter(|| $mutate(black_box(&mut cfg)))
+                });
+        }
+    };
+}
+
+fn representations(c: &mut Criterion) {
+    bench_backend!(c, "trait_object", trait_object_config, config_trait_object::Config,
+        build: |cfg: config_trait_object::Config| {
+            for name in cfg.provider_config.names() {
+                cfg.provider_config.try_get(name).unwrap();
+            }
+            cfg
+        },
+        lookup: |cfg: &config_trait_object::Config| read_provider(cfg.current_provider_config().unwrap()),
+        mutate: |cfg: &mut config_trait_object::Config| {
+            let mut pcfg = dyn_clone::clone_box(cfg.current_provider_config().unwrap());
+            pcfg.set_token("new_token".to_string());
+            cfg.provider_config.set(&name(0), pcfg);
+        });
+    bench_backend!(c, "enum", enum_config, config_enum::Config,
+        build: |cfg: config_enum::Config| cfg,
+        lookup: |cfg: &config_enum::Config| read_provider(cfg.current_provider_config().unwrap()),
+        mutate: |cfg: &mut config_enum::Config| {
+            cfg.provider_config.get_mut(&name(0)).unwrap().set_token("new_token".to_string());
+        });
+    bench_backend!(c, "struct", struct_config, config_struct::Config,
+        build: |cfg: config_struct::Config| cfg,
+        lookup: |cfg: &config_struct::Config| {
+            let pcfg = cfg.current_provider_config().unwrap();
+            pcfg.api_base.as_ref().map_or(0, |s| s.len()) + pcfg.token.as_ref().map_or(0, |s| s.len()) + pcfg.model.as_ref().map_or(0, |s| s.len())
+        },
+        mutate: |cfg: &mut config_struct::Config| {
+            cfg.provider_config.get_mut(&name(0)).unwrap().token = Some("new_token".to_string());
+        });
+    bench_backend!(c, "boxed_trait", boxed_trait_config, config_struct_boxed_trait::Config,
+        build: |cfg: config_struct_boxed_trait::Config| cfg,
+        lookup: |cfg: &config_struct_boxed_trait::Config| {
+            let pcfg = cfg.current_provider_config().unwrap();
+            let pcfg = pcfg.as_any().downcast_ref::<config_struct_boxed_trait::OpenaiMobile>().unwrap();
+            pcfg.api_base.as_ref().map_or(0, |s| s.len()) + pcfg.token.as_ref().map_or(0, |s| s.len()) + pcfg.model.as_ref().map_or(0, |s| s.len())
+        },
+        mutate: |cfg: &mut config_struct_boxed_trait::Config| {
+            cfg.provider_config.openai_mobile.get_mut(&name(0)).unwrap().token = Some("new_token".to_string());
+        });
+}
+
+/// the trait object config as it was before provider configs became lazy: typetag deserializes
+/// every entry of the map while loading
+#[derive(serde::Deserialize)]
+struct EagerConfig {
+    provider: Option<String>,
+    provider_config: HashMap<String, Box<dyn config_trait_object::ProviderConfig>>,
+}
+
+/// loading a config to use its current provider, the trait object backend deserializes entries on first
+/// access (`lazy`), `eager` is typetag deserializing the whole map into `Box<dyn ProviderConfig>`s
+fn startup(c: &mut Criterion) {
+    for &n in SIZES {
+        let yaml = serde_yaml::to_string(&trait_object_config(n)).unwrap();
+        let mut group = c.benchmark_group("startup");
+        group.bench_with_input(BenchmarkId::new("lazy", n), &yaml, |b, yaml| {
+            b.iter(|| {
+                let cfg = serde_yaml::from_str::<config_trait_object::Config>(black_box(yaml)).unwrap();
+                read_provider(cfg.current_provider_config().unwrap())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("eager", n), &yaml, |b, yaml| {
+            b.iter(|| {
+                let cfg = serde_yaml::from_str::<EagerConfig>(black_box(yaml)).unwrap();
+                read_provider(cfg.provider_config[cfg.provider.as_deref().unwrap()].as_ref())
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = representations, startup
+}
+criterion_main!(benches);
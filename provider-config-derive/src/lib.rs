@@ -0,0 +1,259 @@
+//! `#[derive(ProviderAccess)]` implements `provider::ProviderAccess` and `Default`,
+//! `#[derive(ProviderConfig)]` does the same and also implements `config_trait_object::ProviderConfig`
+//!
+//! the accessors are generated from the well known field names:
+//! `api_base`, `token`, `model` (`Option<String>`), `cookies` (`HashMap<String, String>`),
+//! `timeout` (`Option<u64>` seconds) and `expires_at` (`Option<chrono::DateTime<Utc>>`).
+//! a method whose field is missing returns an empty value, or the value given on the struct,
+//! e.g. `#[provider(model = "claude2")]`.
+//!
+//! the generated code names the library as `::serde_trait_object_demo`, which also resolves inside it,
+//! a crate depending on it under another name sets `#[provider(crate = "path")]` on the struct.
+//!
+//! field attributes:
+//! - `#[provider(default = "...")]` the value used by `Default` and as the accessor fallback
+//! - `#[provider(required)]` `validate()` fails when the field is missing or empty
+//!
+//! the `ProviderConfig` impl is registered with typetag, so the type name is the `provider` tag,
+//! and submitted as a `config_trait_object::ProviderKind`, so a map key with that name is checked against the tag.
+//! its `schema()` is a JSON schema of the fields built from the same names and attributes.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+#[proc_macro_derive(ProviderAccess, attributes(provider))]
+pub fn derive_provider_access(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input, false).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+#[proc_macro_derive(ProviderConfig, attributes(provider))]
+pub fn derive_provider_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input, true).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+struct Field {
+    ident: syn::Ident,
+    default: Option<LitStr>,
+    required: bool,
+}
+
+fn expand(input: DeriveInput, provider_config: bool) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input, "can only be derived for structs"));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input, "needs named fields"));
+    };
+
+    let mut fixed: Vec<(String, LitStr)> = Vec::new();
+    let mut krate: syn::Path = syn::parse_quote!(::serde_trait_object_demo);
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("provider")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                return Ok(());
+            }
+            let key = meta.path.get_ident()
+                .map(|ident| ident.to_string())
+                .ok_or_else(|| meta.error("expected `api_base`, `token`, `model` or `crate`"))?;
+            if !["api_base", "token", "model"].contains(&key.as_str()) {
+                return Err(meta.error("expected `api_base`, `token`, `model` or `crate`"));
+            }
+            fixed.push((key, meta.value()?.parse()?));
+            Ok(())
+        })?;
+    }
+
+    let mut fields = Vec::new();
+    for field in &named.named {
+        let mut parsed = Field { ident: field.ident.clone().unwrap(), default: None, required: false };
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("provider")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    parsed.default = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("required") {
+                    parsed.required = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `default = \"...\"` or `required`"))
+                }
+            })?;
+        }
+        fields.push(parsed);
+    }
+
+    let field = |name: &str| fields.iter().find(|f| f.ident == name);
+    let fixed = |name: &str| fixed.iter().find(|(key, _)| key == name).map(|(_, value)| value);
+
+    let string_accessor = |name: &str| -> TokenStream2 {
+        match (field(name), fixed(name)) {
+            (Some(Field { ident, default: Some(default), .. }), _) => {
+                quote! { self.#ident.clone().unwrap_or_else(|| #default.to_string()) }
+            }
+            (Some(Field { ident, .. }), _) => quote! { self.#ident.clone().unwrap_or_default() },
+            (None, Some(value)) => quote! { #value.to_string() },
+            (None, None) => quote! { ::std::string::String::new() },
+        }
+    };
+    let api_base = string_accessor("api_base");
+    let token = string_accessor("token");
+
+    let model = match (field("model"), fixed("model")) {
+        (Some(Field { ident, default: Some(default), .. }), _) => {
+            quote! { ::std::option::Option::Some(self.#ident.clone().unwrap_or_else(|| #default.to_string())) }
+        }
+        (Some(Field { ident, .. }), _) => quote! { self.#ident.clone() },
+        (None, Some(value)) => quote! { ::std::option::Option::Some(#value.to_string()) },
+        (None, None) => quote! { ::std::option::Option::None },
+    };
+
+    let set_token = match field("token") {
+        Some(Field { ident, .. }) => quote! { self.#ident = ::std::option::Option::Some(token); },
+        None => quote! { let _ = token; },
+    };
+
+    let (cookies, set_cookies) = match field("cookies") {
+        Some(Field { ident, .. }) => (quote! { ::std::option::Option::Some(self.#ident.clone()) }, quote! { self.#ident = cookies; }),
+        None => (quote! { ::std::option::Option::None }, quote! { let _ = cookies; }),
+    };
+
+    let (expires_at, set_expires_at) = match field("expires_at") {
+        Some(Field { ident, .. }) => (quote! { self.#ident }, quote! { self.#ident = expires_at; }),
+        None => (quote! { ::std::option::Option::None }, quote! { let _ = expires_at; }),
+    };
+
+    let timeout = match field("timeout") {
+        Some(Field { ident, .. }) => quote! { self.#ident.map(::std::time::Duration::from_secs) },
+        None => quote! { ::std::option::Option::None },
+    };
+
+    let checks = fields.iter().filter(|f| f.required).map(|Field { ident, .. }| {
+        let message = format!("{} is empty", ident);
+        let check = if ident == "cookies" {
+            quote! { self.#ident.is_empty() }
+        } else {
+            quote! { self.#ident.as_deref().unwrap_or_default().is_empty() }
+        };
+        quote! {
+            if #check {
+                #krate::__private::anyhow::bail!(#message);
+            }
+        }
+    });
+
+    let defaults = fields.iter().map(|Field { ident, default, .. }| match default {
+        Some(default) => quote! { #ident: ::std::option::Option::Some(#default.to_string()) },
+        None => quote! { #ident: ::std::default::Default::default() },
+    });
+
+    let properties = fields.iter().map(|Field { ident, default, .. }| {
+        let key = ident.to_string();
+        let mut schema = match key.as_str() {
+            "api_base" | "token" | "model" => quote! { #krate::__private::serde_json::json!({ "type": "string" }) },
+            "cookies" => quote! { #krate::__private::serde_json::json!({ "type": "object", "additionalProperties": { "type": "string" } }) },
+            "timeout" => quote! { #krate::__private::serde_json::json!({ "type": "integer", "minimum": 0 }) },
+            "expires_at" => quote! { #krate::__private::serde_json::json!({ "type": "string", "format": "date-time" }) },
+            _ => quote! { #krate::__private::serde_json::json!({}) },
+        };
+        if let Some(default) = default {
+            schema = quote! {{
+                let mut schema = #schema;
+                schema["default"] = #krate::__private::serde_json::Value::from(#default);
+                schema
+            }};
+        }
+        quote! { properties.insert(#key.to_string(), #schema); }
+    });
+    let kind = name.to_string();
+    let required = fields.iter().filter(|f| f.required).map(|f| f.ident.to_string());
+
+    // typetag's expansion names `typetag::` relative to the impl, so it is imported from the library
+    let registration = provider_config.then(|| quote! {
+        const _: () = {
+            use #krate::__private::typetag;
+
+            #krate::__private::inventory::submit! {
+                #krate::config_trait_object::ProviderKind::new::<#name>(#kind)
+            }
+
+            #[typetag::serde]
+            impl #krate::config_trait_object::ProviderConfig for #name {
+                fn as_any(&self) -> &dyn ::std::any::Any {
+                    self
+                }
+
+                fn schema(&self) -> #krate::__private::serde_json::Value {
+                    let mut properties = #krate::__private::serde_json::Map::new();
+                    properties.insert("provider".to_string(), #krate::__private::serde_json::json!({ "const": #kind }));
+                    #(#properties)*
+                    #krate::__private::serde_json::json!({
+                        "type": "object",
+                        "properties": properties,
+                        "required": ["provider", #(#required),*],
+                    })
+                }
+            }
+        };
+    });
+
+    Ok(quote! {
+        impl ::std::default::Default for #name {
+            fn default() -> Self {
+                #name {
+                    #(#defaults,)*
+                }
+            }
+        }
+
+        impl #krate::provider::ProviderAccess for #name {
+            fn api_base(&self) -> ::std::string::String {
+                #api_base
+            }
+
+            fn token(&self) -> ::std::string::String {
+                #token
+            }
+
+            fn set_token(&mut self, token: ::std::string::String) {
+                #set_token
+            }
+
+            fn cookies(&self) -> ::std::option::Option<::std::collections::HashMap<::std::string::String, ::std::string::String>> {
+                #cookies
+            }
+
+            fn set_cookies(&mut self, cookies: ::std::collections::HashMap<::std::string::String, ::std::string::String>) {
+                #set_cookies
+            }
+
+            fn model(&self) -> ::std::option::Option<::std::string::String> {
+                #model
+            }
+
+            fn timeout(&self) -> ::std::option::Option<::std::time::Duration> {
+                #timeout
+            }
+
+            fn expires_at(&self) -> ::std::option::Option<#krate::__private::chrono::DateTime<#krate::__private::chrono::Utc>> {
+                #expires_at
+            }
+
+            fn set_expires_at(&mut self, expires_at: ::std::option::Option<#krate::__private::chrono::DateTime<#krate::__private::chrono::Utc>>) {
+                #set_expires_at
+            }
+
+            fn validate(&self) -> #krate::__private::anyhow::Result<()> {
+                #(#checks)*
+                ::std::result::Result::Ok(())
+            }
+        }
+
+        #registration
+    })
+}
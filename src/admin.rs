@@ -0,0 +1,232 @@
+//! a local HTTP API to view and edit a `config_trait_object::Config`, for ops dashboards
+//!
+//! - `GET /providers` the active provider and every entry
+//! - `GET /providers/{name}` and `PUT /providers/{name}` one entry, as JSON with its `provider` tag
+//! - `POST /active` with `{"provider": "<name>"}` makes `<name>` the only selected provider
+//! - `GET /schema` the JSON schema of every provider kind
+//!
+//! `token` and cookie values are redacted in responses, sending the redacted value back keeps the stored one.
+//! every response carries an `ETag` of the whole config, a write with a stale `If-Match` fails with 412,
+//! and a write is validated before it is applied.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::config_trait_object::{is_provider_kind, provider_kinds, Config, ProviderConfig};
+use crate::shared::SharedConfig;
+
+/// what `token` and each cookie value read as
+pub const REDACTED: &str = "***";
+
+pub fn router(config: SharedConfig) -> Router {
+    Router::new()
+        .route("/providers", get(list_providers))
+        .route("/providers/{name}", get(get_provider).put(put_provider))
+        .route("/active", post(set_active))
+        .route("/schema", get(schema))
+        .with_state(config)
+}
+
+/// serve the admin API until the listener fails
+pub async fn serve(listener: tokio::net::TcpListener, config: SharedConfig) -> anyhow::Result<()> {
+    axum::serve(listener, router(config)).await?;
+    Ok(())
+}
+
+#[derive(Debug)]
+struct ApiError(StatusCode, String);
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        debug!("admin api: {} {}", self.0, self.1);
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// an `ApiError` returned through `SharedConfig::update` keeps its status
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        e.downcast::<ApiError>()
+            .unwrap_or_else(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+}
+
+async fn list_providers(State(config): State<SharedConfig>) -> Result<Response, ApiError> {
+    let config = config.snapshot();
+    let mut names: Vec<&str> = config.provider_config.names().collect();
+    names.sort();
+    let mut providers = serde_json::Map::new();
+    for name in names {
+        let entry = match entry_json(&config, name) {
+            Ok(entry) => entry,
+            Err(ApiError(_, e)) => serde_json::json!({ "error": e }),
+        };
+        providers.insert(name.to_string(), entry);
+    }
+    let body = serde_json::json!({ "active": config.provider(), "providers": providers });
+    with_etag(&config, body)
+}
+
+async fn get_provider(State(config): State<SharedConfig>, Path(name): Path<String>) -> Result<Response, ApiError> {
+    let config = config.snapshot();
+    let entry = entry_json(&config, &name)?;
+    with_etag(&config, entry)
+}
+
+async fn put_provider(
+    State(config): State<SharedConfig>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Result<Response, ApiError> {
+    let (entry, etag) = config.update(|config| {
+        check_if_match(config, &headers)?;
+
+        if let Some(existing) = config.provider_config.get(&name) {
+            unredact(&mut body, &serde_json::to_value(existing)?);
+        }
+        let pcfg: Box<dyn ProviderConfig> = serde_json::from_value(body)
+            .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("invalid provider config {}: {}", name, e)))?;
+        if is_provider_kind(&name) && pcfg.kind() != name {
+            return Err(ApiError(StatusCode::UNPROCESSABLE_ENTITY,
+                                format!("provider config key {} does not match its provider tag {}", name, pcfg.kind())).into());
+        }
+        pcfg.validate()
+            .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("provider config {}: {}", name, e)))?;
+
+        debug!("admin api: set provider config {}", name);
+        config.provider_config.set(&name, pcfg);
+        Ok((entry_json(config, &name)?, etag(config)?))
+    })?;
+    Ok(([(header::ETAG, etag)], Json(entry)).into_response())
+}
+
+#[derive(Deserialize)]
+struct SetActive {
+    provider: String,
+}
+
+async fn set_active(
+    State(config): State<SharedConfig>,
+    headers: HeaderMap,
+    Json(body): Json<SetActive>,
+) -> Result<Response, ApiError> {
+    let etag = config.update(|config| {
+        check_if_match(config, &headers)?;
+
+        match config.provider_config.try_get(&body.provider) {
+            Ok(Some(pcfg)) => pcfg.validate()
+                .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("provider config {}: {}", body.provider, e)))?,
+            Ok(None) => return Err(ApiError(StatusCode::NOT_FOUND, format!("no provider config {}", body.provider)).into()),
+            Err(e) => return Err(ApiError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into()),
+        }
+
+        debug!("admin api: set active provider {}", body.provider);
+        config.provider = Some(body.provider.clone().into());
+        etag(config)
+    })?;
+    Ok(([(header::ETAG, etag)], Json(serde_json::json!({ "active": body.provider }))).into_response())
+}
+
+async fn schema() -> Json<Value> {
+    let kinds: serde_json::Map<String, Value> = provider_kinds().into_iter()
+        .map(|kind| (kind.name().to_string(), kind.default_config().schema()))
+        .collect();
+    Json(serde_json::json!({ "kinds": kinds }))
+}
+
+/// the redacted JSON of entry `name`
+fn entry_json(config: &Config, name: &str) -> Result<Value, ApiError> {
+    let pcfg = match config.provider_config.try_get(name) {
+        Ok(Some(pcfg)) => pcfg,
+        Ok(None) => return Err(ApiError(StatusCode::NOT_FOUND, format!("no provider config {}", name))),
+        Err(e) => return Err(ApiError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())),
+    };
+    let mut entry = serde_json::to_value(pcfg).map_err(anyhow::Error::from)?;
+    redact(&mut entry);
+    Ok(entry)
+}
+
+fn redact(entry: &mut Value) {
+    if let Some(token) = entry.get_mut("token").filter(|token| token.is_string()) {
+        *token = REDACTED.into();
+    }
+    if let Some(Value::Object(cookies)) = entry.get_mut("cookies") {
+        for value in cookies.values_mut() {
+            *value = REDACTED.into();
+        }
+    }
+}
+
+/// put back the stored `token` and cookie values where `entry` holds the redacted ones
+fn unredact(entry: &mut Value, existing: &Value) {
+    if let Some(token) = entry.get_mut("token").filter(|token| *token == REDACTED) {
+        *token = existing.get("token").cloned().unwrap_or(Value::Null);
+    }
+    if let Some(Value::Object(cookies)) = entry.get_mut("cookies") {
+        for (key, value) in cookies.iter_mut().filter(|(_, value)| **value == REDACTED) {
+            if let Some(stored) = existing.get("cookies").and_then(|cookies| cookies.get(key)) {
+                *value = stored.clone();
+            }
+        }
+    }
+}
+
+/// a hash of the config, the same for equal configs whatever the order of their maps
+fn etag(config: &Config) -> anyhow::Result<String> {
+    let mut hasher = DefaultHasher::new();
+    hash_value(&serde_json::to_value(config)?, &mut hasher);
+    Ok(format!("\"{:016x}\"", hasher.finish()))
+}
+
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            hasher.write_u8(b'o');
+            for (key, value) in entries {
+                key.hash(hasher);
+                hash_value(value, hasher);
+            }
+        }
+        Value::Array(items) => {
+            hasher.write_u8(b'a');
+            items.len().hash(hasher);
+            for item in items {
+                hash_value(item, hasher);
+            }
+        }
+        value => value.to_string().hash(hasher),
+    }
+}
+
+fn check_if_match(config: &Config, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(if_match) = headers.get(header::IF_MATCH) else {
+        return Ok(());
+    };
+    let etag = etag(config)?;
+    if if_match != "*" && if_match.as_bytes() != etag.as_bytes() {
+        return Err(ApiError(StatusCode::PRECONDITION_FAILED, format!("config changed, current etag is {}", etag)));
+    }
+    Ok(())
+}
+
+fn with_etag(config: &Config, body: Value) -> Result<Response, ApiError> {
+    Ok(([(header::ETAG, etag(config)?)], Json(body)).into_response())
+}
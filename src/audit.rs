@@ -0,0 +1,170 @@
+//! audit events for config changes: load, save, token change and provider switch
+//!
+//! every event is logged with target `audit` and its fields as structured key-values,
+//! and appended as one JSON line to the audit file, when one is set with [`set_audit_file`].
+//! tokens never appear in an event, only a fingerprint telling whether two of them are equal.
+//!
+//! token changes and switches are audited when they go through `Config::transaction`, `SharedConfig`,
+//! a patch or `ConfigStore::set_active`, not when `Config` fields are assigned directly
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::config_trait_object::Config;
+use crate::diff::MASK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Load,
+    Save,
+    TokenChange,
+    ProviderSwitch,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub actor: String,
+    /// the provider instance switched to or whose field changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    /// the file loaded or saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+static ACTOR: RwLock<Option<String>> = RwLock::new(None);
+static AUDIT_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// who the following events are attributed to, `$USER` by default
+pub fn set_actor(actor: &str) {
+    *ACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(actor.to_string());
+}
+
+pub fn actor() -> String {
+    match &*ACTOR.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(actor) => actor.clone(),
+        None => std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+    }
+}
+
+/// append every following event to `path` as a JSON line, the file is created `0600`
+pub fn set_audit_file<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+    let file = OpenOptions::new().append(true).create(true).mode(0o600).open(path)?;
+    *AUDIT_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    Ok(())
+}
+
+/// stop writing the audit file
+pub fn close_audit_file() {
+    *AUDIT_FILE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// `***` and a fingerprint of the value, empty for an empty value
+pub fn redact(value: &str) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{}{:08x}", MASK, hasher.finish() as u32)
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        AuditEvent {
+            timestamp: Utc::now(),
+            action,
+            actor: actor(),
+            provider: None,
+            field: None,
+            old: None,
+            new: None,
+            path: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        AuditEvent { path: Some(path.display().to_string()), ..AuditEvent::new(AuditAction::Load) }
+    }
+
+    pub fn save(path: &Path) -> Self {
+        AuditEvent { path: Some(path.display().to_string()), ..AuditEvent::new(AuditAction::Save) }
+    }
+
+    pub fn token_change(provider: &str, old: &str, new: &str) -> Self {
+        AuditEvent {
+            provider: Some(provider.to_string()),
+            field: Some("token".to_string()),
+            old: Some(redact(old)),
+            new: Some(redact(new)),
+            ..AuditEvent::new(AuditAction::TokenChange)
+        }
+    }
+
+    pub fn provider_switch(old: &str, new: &str) -> Self {
+        AuditEvent {
+            provider: Some(new.to_string()),
+            field: Some("provider".to_string()),
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+            ..AuditEvent::new(AuditAction::ProviderSwitch)
+        }
+    }
+
+    /// log the event and append it to the audit file, a failing audit file is logged, not returned
+    pub fn emit(&self) {
+        log::info!(
+            target: "audit",
+            timestamp:serde = self.timestamp,
+            action:serde = self.action,
+            actor = self.actor.as_str(),
+            provider:serde = self.provider,
+            field:serde = self.field,
+            old:serde = self.old,
+            new:serde = self.new,
+            path:serde = self.path;
+            "{:?} by {}", self.action, self.actor
+        );
+
+        let mut file = AUDIT_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.as_mut() {
+            let written = serde_json::to_string(self).map_err(anyhow::Error::from)
+                .and_then(|line| Ok(file.write_all(format!("{}\n", line).as_bytes())?));
+            if let Err(e) = written {
+                log::warn!(target: "audit", "write audit file: {}", e);
+            }
+        }
+    }
+}
+
+/// emit a switch when the active provider differs, and a token change for each of `names` whose token differs
+pub(crate) fn audit_changes<'a>(old: &Config, new: &Config, names: impl IntoIterator<Item = &'a str>) {
+    let (old_provider, new_provider) = (old.provider(), new.provider());
+    if old_provider != new_provider {
+        AuditEvent::provider_switch(&old_provider, &new_provider).emit();
+    }
+    for name in names {
+        let (Some(old_pcfg), Some(new_pcfg)) = (old.provider_config.get(name), new.provider_config.get(name)) else {
+            continue;
+        };
+        let (old_token, new_token) = (old_pcfg.token(), new_pcfg.token());
+        if old_token != new_token {
+            AuditEvent::token_change(name, &old_token, &new_token).emit();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! render the criterion results of `cargo bench --bench representations` as markdown tables,
+//! one table per benchmark group, one row per config size and one column per backend
+//!
+//! usage: `cargo run --bin bench_report [target/criterion] > BENCHMARKS.md`
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const GROUPS: &[&str] = &[
+    "deserialize_yaml", "serialize_yaml", "deserialize_json", "serialize_json", "clone", "lookup", "mutate", "startup",
+];
+const BACKENDS: &[&str] = &["trait_object", "enum", "struct", "boxed_trait"];
+
+fn main() -> anyhow::Result<()> {
+    let root = std::env::args().nth(1).unwrap_or_else(|| "target/criterion".to_string());
+    let root = Path::new(&root);
+    if !root.is_dir() {
+        anyhow::bail!("{} not found, run `cargo bench --bench representations` first", root.display());
+    }
+
+    println!("# benchmarks\n");
+    println!("mean time per iteration, generated by `cargo run --bin bench_report`\n");
+    for group in GROUPS {
+        // size -> backend -> mean in ns
+        let mut results: BTreeMap<usize, BTreeMap<String, f64>> = BTreeMap::new();
+        for backend in std::fs::read_dir(root.join(group)).into_iter().flatten().flatten() {
+            let backend_name = backend.file_name().to_string_lossy().to_string();
+            for size in std::fs::read_dir(backend.path())?.flatten() {
+                let Ok(n) = size.file_name().to_string_lossy().parse::<usize>() else {
+                    continue;
+                };
+                let estimates = size.path().join("new").join("estimates.json");
+                let Ok(contents) = std::fs::read_to_string(&estimates) else {
+                    continue;
+                };
+                let estimates: serde_json::Value = serde_json::from_str(&contents)?;
+                let mean = estimates["mean"]["point_estimate"].as_f64()
+                    .ok_or_else(|| anyhow::format_err!("no mean in {}", size.path().display()))?;
+                results.entry(n).or_default().insert(backend_name.clone(), mean);
+            }
+        }
+        if results.is_empty() {
+            continue;
+        }
+
+        // the known backends first, then any other ids of the group, e.g. `lazy` and `eager` of `startup`
+        let mut backends: Vec<&str> = BACKENDS.iter().copied()
+            .filter(|backend| results.values().any(|row| row.contains_key(*backend)))
+            .collect();
+        let others: std::collections::BTreeSet<&str> = results.values()
+            .flat_map(|row| row.keys().map(|id| id.as_str()))
+            .filter(|id| !BACKENDS.contains(id))
+            .collect();
+        backends.extend(others);
+        println!("## {}\n", group);
+        println!("| entries | {} |", backends.join(" | "));
+        println!("|---:|{}", "---:|".repeat(backends.len()));
+        for (n, row) in &results {
+            let cells: Vec<String> = backends.iter()
+                .map(|backend| row.get(*backend).map_or("-".to_string(), |ns| format_duration(*ns)))
+                .collect();
+            println!("| {} | {} |", n, cells.join(" | "));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn format_duration(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{:.1} ns", ns)
+    } else if ns < 1e6 {
+        format!("{:.2} µs", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.2} ms", ns / 1e6)
+    } else {
+        format!("{:.2} s", ns / 1e9)
+    }
+}
@@ -0,0 +1,29 @@
+//! serve a config to worker processes
+//!
+//! usage: `cargo run --bin config_daemon <config.yaml> <loopback ip:port | unix socket path>`,
+//! anything that is not an `ip:port` is a socket path, which may contain `:`
+
+use std::net::SocketAddr;
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::daemon::{bind_tcp, bind_unix, Daemon};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let mut args = std::env::args().skip(1);
+    let (Some(path), Some(listen)) = (args.next(), args.next()) else {
+        anyhow::bail!("usage: config_daemon <config.yaml> <loopback ip:port | unix socket path>");
+    };
+    let config = Config::load(&path)?;
+    let daemon = Daemon::new(config);
+
+    if let Ok(addr) = listen.parse::<SocketAddr>() {
+        let listener = bind_tcp(addr).await?;
+        println!("serving {} on {}", path, listener.local_addr()?);
+        daemon.serve_tcp(listener).await
+    } else {
+        let listener = bind_unix(&listen)?;
+        println!("serving {} on {}", path, listen);
+        daemon.serve_unix(listener).await
+    }
+}
@@ -0,0 +1,55 @@
+//! list, diff and restore the saved snapshots of a config file
+//!
+//! usage:
+//! - `config_history list <config.yaml>`
+//! - `config_history diff <config.yaml> <id> [<id>]`, the second defaults to the file as it is now
+//! - `config_history record <config.yaml> [<note>]` snapshot the file as it is now
+//! - `config_history restore <config.yaml> <id>`
+//!
+//! the author is `$USER`
+
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::history::{summarize, History};
+
+const USAGE: &str = "usage: config_history list|diff|record|restore <config.yaml> [args]";
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (Some(command), Some(path)) = (args.first(), args.get(1)) else {
+        anyhow::bail!(USAGE);
+    };
+    let history = History::for_config(path);
+    let author = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+    let id = |i: usize| -> anyhow::Result<u64> {
+        let id = args.get(i).ok_or_else(|| anyhow::format_err!(USAGE))?;
+        id.trim_start_matches('#').parse().map_err(|_| anyhow::format_err!("invalid snapshot id {}", id))
+    };
+
+    match command.as_str() {
+        "list" => {
+            for entry in history.list()? {
+                println!("#{} {} {}: {}", entry.id, entry.timestamp.to_rfc3339(), entry.author, entry.summary.join("; "));
+            }
+        }
+        "diff" => {
+            let changes = match args.get(3) {
+                Some(_) => history.diff(id(2)?, id(3)?)?,
+                None => summarize(&history.load(id(2)?)?, &Config::load(path)?)?,
+            };
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+        "record" => {
+            let entry = history.record(&Config::load(path)?, &author, args.get(2).map(|note| note.as_str()))?;
+            println!("recorded #{}: {}", entry.id, entry.summary.join("; "));
+        }
+        "restore" => {
+            let entry = history.restore(id(2)?, path, &author)?;
+            println!("restored as #{}: {}", entry.id, entry.summary.join("; "));
+        }
+        _ => anyhow::bail!(USAGE),
+    }
+    Ok(())
+}
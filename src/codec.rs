@@ -0,0 +1,61 @@
+//! length-delimited JSON frames of `config_trait_object::Config` and `Box<dyn ProviderConfig>`,
+//! for streaming config updates over a socket with `tokio_util::codec::Framed`
+
+use std::marker::PhantomData;
+use bytes::{Bytes, BytesMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+use crate::config_trait_object::{Config, ProviderConfig};
+
+/// each frame is a 4 byte big endian length followed by the JSON of one `T`
+///
+/// anything serializable can be encoded, e.g. `&Config` or `&dyn ProviderConfig`, so senders need not clone
+pub struct JsonFrameCodec<T> {
+    frames: LengthDelimitedCodec,
+    item: PhantomData<fn() -> T>,
+}
+
+pub type ConfigCodec = JsonFrameCodec<Config>;
+pub type ProviderConfigCodec = JsonFrameCodec<Box<dyn ProviderConfig>>;
+
+impl<T> JsonFrameCodec<T> {
+    pub fn new() -> Self {
+        JsonFrameCodec { frames: LengthDelimitedCodec::new(), item: PhantomData }
+    }
+
+    /// frames longer than `max` bytes are rejected, the default is 8 MiB
+    pub fn with_max_frame_length(max: usize) -> Self {
+        let mut codec = Self::new();
+        codec.frames.set_max_frame_length(max);
+        codec
+    }
+}
+
+impl<T> Default for JsonFrameCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, I: Serialize> Encoder<I> for JsonFrameCodec<T> {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(&item)?;
+        self.frames.encode(Bytes::from(json), dst)?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for JsonFrameCodec<T> {
+    type Item = T;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> anyhow::Result<Option<T>> {
+        match self.frames.decode(src)? {
+            Some(frame) => Ok(Some(serde_json::from_slice(&frame)?)),
+            None => Ok(None),
+        }
+    }
+}
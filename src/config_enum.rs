@@ -1,8 +1,12 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use dyn_clone::DynClone;
 use log::{debug};
+use crate::provider::ProviderAccess;
+use crate::store::{ConfigStore, ProviderView};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -18,6 +22,89 @@ pub enum ProviderConfig  {
     Claude2(Claude2),
 }
 
+impl ProviderConfig {
+    /// the provider kind, the map key is the instance name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProviderConfig::OpenaiMobile(_) => "OpenaiMobile",
+            ProviderConfig::Claude2(_) => "Claude2",
+        }
+    }
+}
+
+/// static dispatch to the variant, the counterpart of `Box<dyn ProviderConfig>` in `config_trait_object`
+impl ProviderAccess for ProviderConfig {
+    fn api_base(&self) -> String {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.api_base(),
+            ProviderConfig::Claude2(pcfg) => pcfg.api_base(),
+        }
+    }
+
+    fn token(&self) -> String {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.token(),
+            ProviderConfig::Claude2(pcfg) => pcfg.token(),
+        }
+    }
+
+    fn set_token(&mut self, token: String) {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.set_token(token),
+            ProviderConfig::Claude2(pcfg) => pcfg.set_token(token),
+        }
+    }
+
+    fn cookies(&self) -> Option<HashMap<String, String>> {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.cookies(),
+            ProviderConfig::Claude2(pcfg) => pcfg.cookies(),
+        }
+    }
+
+    fn set_cookies(&mut self, cookies: HashMap<String, String>) {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.set_cookies(cookies),
+            ProviderConfig::Claude2(pcfg) => pcfg.set_cookies(cookies),
+        }
+    }
+
+    fn model(&self) -> Option<String> {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.model(),
+            ProviderConfig::Claude2(pcfg) => pcfg.model(),
+        }
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.timeout(),
+            ProviderConfig::Claude2(pcfg) => pcfg.timeout(),
+        }
+    }
+
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.expires_at(),
+            ProviderConfig::Claude2(pcfg) => pcfg.expires_at(),
+        }
+    }
+
+    fn set_expires_at(&mut self, expires_at: Option<DateTime<Utc>>) {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.set_expires_at(expires_at),
+            ProviderConfig::Claude2(pcfg) => pcfg.set_expires_at(expires_at),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            ProviderConfig::OpenaiMobile(pcfg) => pcfg.validate(),
+            ProviderConfig::Claude2(pcfg) => pcfg.validate(),
+        }
+    }
+}
+
 impl Debug for ProviderConfigMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ProviderConfigMap")
@@ -27,6 +114,11 @@ impl Debug for ProviderConfigMap {
 }
 
 impl ProviderConfigMap {
+    /// an empty map, `default()` holds one entry per provider kind
+    pub fn new() -> Self {
+        ProviderConfigMap(HashMap::new())
+    }
+
     pub fn get(&self, provider: &str) -> Option<&ProviderConfig> {
         self.0.get(provider)
     }
@@ -34,6 +126,10 @@ impl ProviderConfigMap {
     pub fn set(&mut self, provider: &str, pcfg: ProviderConfig) {
         self.0.insert(provider.to_string(), pcfg);
     }
+
+    pub fn get_mut(&mut self, provider: &str) -> Option<&mut ProviderConfig> {
+        self.0.get_mut(provider)
+    }
 }
 
 impl Default for ProviderConfigMap {
@@ -75,7 +171,7 @@ impl Config {
     pub fn from_str() -> anyhow::Result<Self> {
         let contents: Vec<u8> = r#"
 provider: OpenaiMobile
-#provider: Claude2
+#provider: openai-personal
 provider_config:
   Claude2: !Claude2
     provider: "Claude2"
@@ -91,6 +187,11 @@ provider_config:
     cookies:
         cookie_foo: "foo_value"
         cookie_bar: "bar_value"
+  openai-personal: !OpenaiMobile
+    token: "tk-xxxxxxxx-002"
+    api_base: "https://api.openai.com"
+    model: "davinci"
+    cookies: {}
 "#.into();
         let config: Config = serde_yaml::from_str(&String::from_utf8_lossy(contents.as_slice())).unwrap();
 
@@ -100,43 +201,68 @@ provider_config:
     }
 
     pub fn to_string(&self) -> anyhow::Result<String> {
-        Ok(serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))?)
+        serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))
     }
 }
 
+impl ConfigStore for Config {
+    fn provider(&self) -> Cow<'_, str> {
+        Cow::Owned(self.provider())
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claude2 {
-    pub cookies: HashMap<String, String>,
-    pub api_base: Option<String>,
-}
+    fn provider_config(&self, provider: &str) -> Option<ProviderView<'_>> {
+        let (name, pcfg) = self.provider_config.0.get_key_value(provider)?;
+        Some(ProviderView {
+            name: Cow::Borrowed(name),
+            kind: Cow::Borrowed(pcfg.kind()),
+            api_base: Some(Cow::Owned(pcfg.api_base())),
+            token: Some(pcfg.token()).filter(|token| !token.is_empty()).map(Cow::Owned),
+            model: pcfg.model().map(Cow::Owned),
+            cookies: Cow::Owned(pcfg.cookies().unwrap_or_default()),
+            timeout: pcfg.timeout().map(|timeout| timeout.as_secs()),
+            expires_at: pcfg.expires_at(),
+        }.normalized())
+    }
 
-impl Default for Claude2 {
-    fn default() -> Self {
-        Claude2 {
-            cookies: HashMap::new(),
-            api_base: "https://claude.io".to_string().into(),
+    fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.provider_config.0.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn set_active(&mut self, provider: &str) -> anyhow::Result<()> {
+        if self.provider_config.get(provider).is_none() {
+            anyhow::bail!("no provider config {}", provider);
         }
+        self.provider = Some(provider.to_string());
+        Ok(())
+    }
+
+    fn to_string(&self) -> anyhow::Result<String> {
+        self.to_string()
+    }
+
+    fn from_yaml(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ProviderAccess)]
+#[provider(model = "claude2")]
+pub struct Claude2 {
+    #[provider(required)]
+    pub cookies: HashMap<String, String>,
+    #[provider(default = "https://claude.io")]
+    pub api_base: Option<String>,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ProviderAccess)]
 pub struct OpenaiMobile {
+    #[provider(required)]
     pub token: Option<String>,
+    #[provider(default = "https://api.openai.com")]
     pub api_base: Option<String>,
+    #[provider(default = "davinci")]
     pub model: Option<String>,
     pub cookies: HashMap<String, String>,
 }
-
-impl Default for OpenaiMobile {
-    fn default() -> Self {
-        OpenaiMobile {
-            token: None,
-            api_base: "https://api.openai.com".to_string().into(),
-            model: "davinci".to_string().into(),
-            cookies: HashMap::new(),
-        }
-    }
-}
-
@@ -1,17 +1,48 @@
-use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
-use dyn_clone::DynClone;
 use log::{debug};
+use crate::store::{ConfigStore, ProviderView};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(try_from = "RawConfig")]
 pub struct Config {
     pub provider: Option<String>,
     pub provider_config: HashMap<String, ProviderConfig>,
 }
+
+/// the provider kinds this backend knows
+pub const KINDS: &[&str] = &["openai_mobile", "claude2"];
+
+/// the on-disk shape of `Config`, before provider kinds are checked
+#[derive(Deserialize)]
+struct RawConfig {
+    provider: Option<String>,
+    provider_config: HashMap<String, ProviderConfig>,
+}
+
+impl TryFrom<RawConfig> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawConfig) -> anyhow::Result<Self> {
+        let mut unknown: Vec<String> = raw.provider_config.iter()
+            .map(|(name, pcfg)| (name, pcfg.kind.as_deref().unwrap_or(name)))
+            .filter(|(_, kind)| !KINDS.contains(kind))
+            .map(|(name, kind)| format!("{} ({})", name, kind))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            anyhow::bail!("unknown provider kind of {}, expected one of {:?}", unknown.join(", "), KINDS);
+        }
+        Ok(Config { provider: raw.provider, provider_config: raw.provider_config })
+    }
+}
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProviderConfig {
+    /// the provider kind, one of `KINDS`, defaults to the instance name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
     pub api_base: Option<String>,
     pub token: Option<String>,
     pub model: Option<String>,
@@ -20,14 +51,26 @@ pub struct ProviderConfig {
 
 impl Config {
     /// get current provider config
-    pub fn current_provider_config(&self) -> Option<ProviderConfig> {
-        let provider = self.provider();
-        let provider = provider.as_str();
-        self.provider_config.get(provider).cloned()
+    pub fn current_provider_config(&self) -> Option<&ProviderConfig> {
+        self.provider_config.get(self.provider())
     }
 
-    pub fn provider(&self) -> String {
-        self.provider.clone().unwrap_or_else(|| "openai_mobile".to_string())
+    /// get the provider config of the instance `provider`, or a default one when there is none
+    pub fn provider_config_or_default(&self, provider: &str) -> Cow<'_, ProviderConfig> {
+        match self.provider_config.get(provider) {
+            Some(pcfg) => Cow::Borrowed(pcfg),
+            None => Cow::Owned(ProviderConfig::default()),
+        }
+    }
+
+    /// get the provider kind of the instance `provider`
+    pub fn provider_kind<'a>(&'a self, provider: &'a str) -> Option<&'a str> {
+        let pcfg = self.provider_config.get(provider)?;
+        Some(pcfg.kind.as_deref().unwrap_or(provider))
+    }
+
+    pub fn provider(&self) -> &str {
+        self.provider.as_deref().unwrap_or("openai_mobile")
     }
 
     pub fn from_str() -> anyhow::Result<Self> {
@@ -47,6 +90,12 @@ provider_config:
     cookies:
         cookie_foo: "foo_value"
         cookie_bar: "bar_value"
+  openai-personal:
+    kind: openai_mobile
+    token: "tk-xxxxxxxx-002"
+    api_base: "https://api.openai.com"
+    model: "davinci"
+    cookies: {}
 "#.into();
         let config: Config = serde_yaml::from_str(&String::from_utf8_lossy(contents.as_slice())).unwrap();
 
@@ -56,7 +105,47 @@ provider_config:
     }
 
     pub fn to_string(&self) -> anyhow::Result<String> {
-        Ok(serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))?)
+        serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))
     }
 }
 
+impl ConfigStore for Config {
+    fn provider(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.provider())
+    }
+
+    fn provider_config(&self, provider: &str) -> Option<ProviderView<'_>> {
+        let (name, pcfg) = self.provider_config.get_key_value(provider)?;
+        Some(ProviderView {
+            name: Cow::Borrowed(name),
+            kind: Cow::Borrowed(pcfg.kind.as_deref().unwrap_or(name)),
+            api_base: pcfg.api_base.as_deref().map(Cow::Borrowed),
+            token: pcfg.token.as_deref().map(Cow::Borrowed),
+            model: pcfg.model.as_deref().map(Cow::Borrowed),
+            cookies: Cow::Borrowed(&pcfg.cookies),
+            ..ProviderView::default()
+        }.normalized())
+    }
+
+    fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.provider_config.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn set_active(&mut self, provider: &str) -> anyhow::Result<()> {
+        if !self.provider_config.contains_key(provider) {
+            anyhow::bail!("no provider config {}", provider);
+        }
+        self.provider = Some(provider.to_string());
+        Ok(())
+    }
+
+    fn to_string(&self) -> anyhow::Result<String> {
+        self.to_string()
+    }
+
+    fn from_yaml(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+}
@@ -1,65 +1,159 @@
 use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use serde::{Deserialize, Serialize};
-use dyn_clone::DynClone;
 use log::{debug};
+use crate::store::{ConfigStore, ProviderView};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     pub provider: Option<String>,
     pub provider_config: ProviderConfig,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// provider instances grouped by kind, keyed by instance name,
+/// an instance name is unique across all kinds.
+/// the layout before named instances, one provider config directly under its kind,
+/// still loads as a single instance named after the kind, and is saved in the new layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ProviderInstances")]
 pub struct ProviderConfig  {
-    openai_mobile: OpenaiMobile,
-    claude2: Claude2,
+    pub openai_mobile: HashMap<String, OpenaiMobile>,
+    pub claude2: HashMap<String, Claude2>,
+}
+
+/// the on-disk shape of `ProviderConfig`, before instance names are checked
+#[derive(Deserialize)]
+struct ProviderInstances {
+    #[serde(default)]
+    openai_mobile: Instances<OpenaiMobile>,
+    #[serde(default)]
+    claude2: Instances<Claude2>,
+}
+
+/// the instances of one kind
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Instances<T> {
+    Named(HashMap<String, T>),
+    /// the layout before named instances
+    Single(T),
+}
+
+impl<T> Default for Instances<T> {
+    fn default() -> Self {
+        Instances::Named(HashMap::new())
+    }
+}
+
+impl<T> Instances<T> {
+    fn into_named(self, kind: &str) -> HashMap<String, T> {
+        match self {
+            Instances::Named(instances) => instances,
+            Instances::Single(pcfg) => HashMap::from([(kind.to_string(), pcfg)]),
+        }
+    }
+}
+
+impl TryFrom<ProviderInstances> for ProviderConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: ProviderInstances) -> anyhow::Result<Self> {
+        let openai_mobile = raw.openai_mobile.into_named("openai_mobile");
+        let claude2 = raw.claude2.into_named("claude2");
+        let mut duplicates: Vec<&String> = openai_mobile.keys()
+            .filter(|name| claude2.contains_key(*name))
+            .collect();
+        if !duplicates.is_empty() {
+            duplicates.sort();
+            anyhow::bail!("provider instance names used by more than one kind: {:?}", duplicates);
+        }
+        Ok(ProviderConfig { openai_mobile, claude2 })
+    }
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        ProviderConfig {
+            openai_mobile: HashMap::from([("openai_mobile".to_string(), OpenaiMobile::default())]),
+            claude2: HashMap::from([("claude2".to_string(), Claude2::default())]),
+        }
+    }
 }
 
 pub trait Provider {
 
-    fn name(&self) -> String;
+    /// the provider kind, not the instance name
+    fn name(&self) -> &'static str;
     fn as_any(&self) -> &dyn Any;
 }
 
 impl Debug for dyn Provider {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.name())
+        f.write_str(self.name())
     }
 }
 impl Config {
-    /// get current provider config
-    pub fn current_provider_config(&self) -> Option<Box<dyn Provider>> {
-        let provider = self.provider();
-        let provider = provider.as_str();
-        match provider {
-            "claude2" => Some(Box::new(self.provider_config.claude2.clone())),
-            "openai_mobile" => Some(Box::new(self.provider_config.openai_mobile.clone())),
-            _ => None,
+    /// get current provider config, resolved by instance name
+    pub fn current_provider_config(&self) -> Option<&dyn Provider> {
+        self.provider_config(self.provider())
+    }
+
+    /// get the provider config of the instance `provider`
+    pub fn provider_config(&self, provider: &str) -> Option<&dyn Provider> {
+        if let Some(pcfg) = self.provider_config.openai_mobile.get(provider) {
+            return Some(pcfg);
+        }
+        if let Some(pcfg) = self.provider_config.claude2.get(provider) {
+            return Some(pcfg);
+        }
+        None
+    }
+
+    /// get the `OpenaiMobile` instance `provider`, or a default one when there is none
+    pub fn openai_mobile_or_default(&self, provider: &str) -> Cow<'_, OpenaiMobile> {
+        match self.provider_config.openai_mobile.get(provider) {
+            Some(pcfg) => Cow::Borrowed(pcfg),
+            None => Cow::Owned(OpenaiMobile::default()),
         }
     }
 
-    pub fn provider(&self) -> String {
-        self.provider.clone().unwrap_or_else(|| "OpenaiMobile".to_string())
+    /// get the `Claude2` instance `provider`, or a default one when there is none
+    pub fn claude2_or_default(&self, provider: &str) -> Cow<'_, Claude2> {
+        match self.provider_config.claude2.get(provider) {
+            Some(pcfg) => Cow::Borrowed(pcfg),
+            None => Cow::Owned(Claude2::default()),
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        self.provider.as_deref().unwrap_or("openai_mobile")
     }
 
     pub fn from_str() -> anyhow::Result<Self> {
         let contents: Vec<u8> = r#"
 provider: openai_mobile
-#provider: claude2
+#provider: openai-personal
 provider_config:
   claude2:
-    cookies:
-        cookie1: "cookie1-value"
-        cookie2: "cookie2-value"
-    api_base: "https://claude.io"
+    claude2:
+      cookies:
+          cookie1: "cookie1-value"
+          cookie2: "cookie2-value"
+      api_base: "https://claude.io"
   openai_mobile:
-    token: "tk-xxxxxxxx-001"
-    api_base: "https://api.openai.com"
-    model: "davinci"
-    cookies:
-        cookie_foo: "foo_value"
-        cookie_bar: "bar_value"
+    openai_mobile:
+      token: "tk-xxxxxxxx-001"
+      api_base: "https://api.openai.com"
+      model: "davinci"
+      cookies:
+          cookie_foo: "foo_value"
+          cookie_bar: "bar_value"
+    openai-personal:
+      token: "tk-xxxxxxxx-002"
+      api_base: "https://api.openai.com"
+      model: "davinci"
+      cookies: {}
 "#.into();
         let config: Config = serde_yaml::from_str(&String::from_utf8_lossy(contents.as_slice())).unwrap();
 
@@ -69,10 +163,63 @@ provider_config:
     }
 
     pub fn to_string(&self) -> anyhow::Result<String> {
-        Ok(serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))?)
+        serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))
     }
 }
 
+impl ConfigStore for Config {
+    fn provider(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.provider())
+    }
+
+    fn provider_config(&self, provider: &str) -> Option<ProviderView<'_>> {
+        if let Some((name, pcfg)) = self.provider_config.openai_mobile.get_key_value(provider) {
+            return Some(ProviderView {
+                name: Cow::Borrowed(name),
+                kind: Cow::Borrowed(pcfg.name()),
+                api_base: pcfg.api_base.as_deref().map(Cow::Borrowed),
+                token: pcfg.token.as_deref().map(Cow::Borrowed),
+                model: pcfg.model.as_deref().map(Cow::Borrowed),
+                cookies: Cow::Borrowed(&pcfg.cookies),
+                ..ProviderView::default()
+            }.normalized());
+        }
+        let (name, pcfg) = self.provider_config.claude2.get_key_value(provider)?;
+        Some(ProviderView {
+            name: Cow::Borrowed(name),
+            kind: Cow::Borrowed(pcfg.name()),
+            api_base: pcfg.api_base.as_deref().map(Cow::Borrowed),
+            cookies: Cow::Borrowed(&pcfg.cookies),
+            ..ProviderView::default()
+        }.normalized())
+    }
+
+    fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.provider_config.openai_mobile.keys()
+            .chain(self.provider_config.claude2.keys())
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn set_active(&mut self, provider: &str) -> anyhow::Result<()> {
+        if !self.provider_config.openai_mobile.contains_key(provider)
+            && !self.provider_config.claude2.contains_key(provider) {
+            anyhow::bail!("no provider config {}", provider);
+        }
+        self.provider = Some(provider.to_string());
+        Ok(())
+    }
+
+    fn to_string(&self) -> anyhow::Result<String> {
+        self.to_string()
+    }
+
+    fn from_yaml(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claude2 {
@@ -90,8 +237,8 @@ impl Default for Claude2 {
 }
 
 impl Provider for Claude2 {
-    fn name(&self) -> String {
-        "claude2".to_string()
+    fn name(&self) -> &'static str {
+        "claude2"
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -108,8 +255,8 @@ pub struct OpenaiMobile {
 }
 
 impl Provider for OpenaiMobile {
-    fn name(&self) -> String {
-        "openai_mobile".to_string()
+    fn name(&self) -> &'static str {
+        "openai_mobile"
     }
     fn as_any(&self) -> &dyn Any {
         self
@@ -1,67 +1,669 @@
+use std::any::Any;
+use std::borrow::Cow;
+#[cfg(feature = "binary")]
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use chrono::{DateTime, Utc};
 use dyn_clone::DynClone;
 use log::{debug};
+use crate::store::{ConfigStore, ProviderView};
+pub use provider_config_derive::ProviderConfig;
+use serde_json::value::RawValue;
+use crate::interpolate::{apply, escape, expansions, lookup, restore_templates};
+use crate::raw_entry::{Document, RawEntries, RawEntry};
+use crate::persist::Source;
+use crate::audit::AuditEvent;
+pub use crate::provider::ProviderAccess;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// a provider kind, `#[derive(ProviderConfig)]` submits one for each struct,
+/// a kind implementing `ProviderConfig` by hand submits its own with `inventory::submit!`
+pub struct ProviderKind {
+    name: &'static str,
+    default: fn() -> Box<dyn ProviderConfig>,
+}
+
+inventory::collect!(ProviderKind);
+
+impl ProviderKind {
+    /// `name` is the `provider` tag typetag registered `T` under
+    pub const fn new<T: ProviderConfig + Default + 'static>(name: &'static str) -> Self {
+        ProviderKind { name, default: default_config::<T> }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// a provider config of this kind with its default values
+    pub fn default_config(&self) -> Box<dyn ProviderConfig> {
+        (self.default)()
+    }
+}
+
+fn default_config<T: ProviderConfig + Default + 'static>() -> Box<dyn ProviderConfig> {
+    Box::new(T::default())
+}
+
+/// every provider kind linked into the program, by name
+pub fn provider_kinds() -> Vec<&'static ProviderKind> {
+    let mut kinds: Vec<&'static ProviderKind> = inventory::iter::<ProviderKind>.into_iter().collect();
+    kinds.sort_by_key(|kind| kind.name);
+    kinds
+}
+
+/// `name` is the name of a provider kind, as a map key it then must agree with the `provider` tag
+pub fn is_provider_kind(name: &str) -> bool {
+    inventory::iter::<ProviderKind>.into_iter().any(|kind| kind.name == name)
+}
+
+/// loading runs `${...}` interpolation over the document before any provider config is built,
+/// see [`interpolate`](crate::interpolate::interpolate). `to_string` writes a value back as its template
+/// while it still holds what the template expanded to, and any other value with every `${` escaped.
+/// `Serialize` writes the values as loaded, escaped, so they load back unchanged in any environment
+#[derive(Debug, Clone)]
 pub struct Config {
-    pub provider: Option<String>,
+    pub provider: Option<ProviderSelection>,
+    /// providers tried after the ones listed in `provider`, in order
+    pub fallback: Vec<String>,
+    pub provider_tag: ProviderTag,
+    /// fields every provider config inherits unless it sets them itself
+    pub defaults: serde_json::Map<String, serde_json::Value>,
     pub provider_config: ProviderConfigMap,
+    /// the file the config was loaded from or last saved to, set by `load` and `save`
+    pub source: Option<Source>,
+    /// the templates of the top-level values as loaded
+    pub templates: Templates,
+}
+
+/// the top-level values of a loaded document as written and as expanded, when any held a `${...}`
+#[derive(Clone, Default)]
+pub struct Templates {
+    written: serde_json::Value,
+    expanded: serde_json::Value,
+}
+
+impl Debug for Templates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Templates").finish_non_exhaustive()
+    }
+}
+
+/// the on-disk shape of `Config`, provider configs are kept untyped until the tag is checked
+#[derive(Serialize, Deserialize)]
+struct RawConfig {
+    provider: Option<ProviderSelection>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fallback: Vec<String>,
+    #[serde(default, skip_serializing_if = "ProviderTag::is_inferred")]
+    provider_tag: ProviderTag,
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    defaults: serde_json::Map<String, serde_json::Value>,
+    /// only written, loading reads the entries as JSON text, see `Config::from_document`
+    #[serde(default, skip_deserializing)]
+    provider_config: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let document = Document::deserialize(deserializer)?;
+        Config::from_document(document, &|name| std::env::var(name).ok()).map_err(D::Error::custom)
+    }
+}
+
+impl TryFrom<serde_json::Value> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(value: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(Config::deserialize(value)?)
+    }
+}
+
+impl Config {
+    /// interpolate the document, then check `extends` and the provider tags of its entries
+    ///
+    /// only the top-level values and the entries holding a `${` are parsed for interpolation,
+    /// every other entry stays the JSON text it was read as until first accessed
+    fn from_document(document: Document, vars: &dyn Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let Document { header, provider_config } = document;
+        let entries = provider_config.ok_or_else(|| anyhow::format_err!("missing field `provider_config`"))?;
+        let mut header = serde_json::Value::Object(header);
+        let (mut provider_config, templated) = ProviderConfigMap::from_entries(entries);
+
+        // `${provider_config.<name>.<field>}` reads the entry as written
+        let references = |name: &str| -> anyhow::Result<Option<serde_json::Value>> {
+            let mut keys = name.split('.');
+            if keys.next() != Some("provider_config") {
+                return Ok(lookup(&header, name.split('.')).cloned());
+            }
+            match keys.next().and_then(|name| provider_config.entries.get(name)) {
+                Some(entry) => Ok(lookup(&entry.written_value()?, keys).cloned()),
+                None => Ok(None),
+            }
+        };
+        let header_expanded = expansions(&header, &[], &references, vars)?;
+        let mut entries_expanded = Vec::with_capacity(templated.len());
+        for name in &templated {
+            let value = provider_config.entries[name].written_value()?;
+            let expanded = expansions(&value, &["provider_config", name], &references, vars)?;
+            entries_expanded.push((name, value, expanded));
+        }
+        let mut templates = Templates::default();
+        if !header_expanded.is_empty() {
+            templates.written = header.clone();
+            apply(&mut header, header_expanded);
+            templates.expanded = header.clone();
+        }
+        for (name, mut value, expanded) in entries_expanded {
+            if !expanded.is_empty() {
+                apply(&mut value, expanded);
+                provider_config.set_expanded(name, value)?;
+            }
+        }
+
+        let raw: RawConfig = serde_json::from_value(header)?;
+        provider_config.defaults = Arc::new(raw.defaults.clone());
+        provider_config.resolve(raw.provider_tag)?;
+        Ok(Config {
+            provider: raw.provider,
+            fallback: raw.fallback,
+            provider_tag: raw.provider_tag,
+            defaults: raw.defaults,
+            provider_config,
+            source: None,
+            templates,
+        })
+    }
+
+    /// the document `to_string` writes, templates included
+    pub fn to_document(&self) -> anyhow::Result<serde_json::Value> {
+        self.document(Output::Templates)
+    }
+
+    fn document(&self, output: Output) -> anyhow::Result<serde_json::Value> {
+        let raw = RawConfig {
+            provider: self.provider.clone(),
+            fallback: self.fallback.clone(),
+            provider_tag: self.provider_tag,
+            defaults: self.defaults.clone(),
+            provider_config: serde_json::Map::new(),
+        };
+        let mut value = serde_json::to_value(raw)?;
+        match output {
+            Output::Values => {}
+            Output::Escaped => escape(&mut value),
+            Output::Templates => restore_templates(&mut value, &self.templates.written, &self.templates.expanded),
+        }
+        value["provider_config"] = serde_json::Value::Object(self.provider_config.to_raw(self.provider_tag, &self.defaults, output)?);
+        Ok(value)
+    }
+}
+
+/// how string values holding a `${` are written
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    /// as they are, for a `ProviderConfigMap` on its own, which loads without interpolation
+    Values,
+    /// every `${` escaped, so the values load back unchanged
+    Escaped,
+    /// as their template where they still hold what it expanded to, escaped otherwise
+    Templates,
+}
+
+impl Serialize for Config {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.document(Output::Escaped).map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+/// how the map key and the embedded `provider` tag of a provider config relate,
+/// a tag present under a kind name key must match it in both modes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderTag {
+    /// every entry carries its `provider` tag, also on save
+    Required,
+    /// the `provider` tag may be omitted when the map key is the kind name,
+    /// and is left out on save in that case, so only one of the two is written
+    #[default]
+    Inferred,
+}
+
+impl ProviderTag {
+    fn is_inferred(&self) -> bool {
+        *self == ProviderTag::Inferred
+    }
+}
+
+/// `provider: OpenaiMobile` or `provider: [OpenaiMobile, Claude2]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProviderSelection {
+    Single(String),
+    Chain(Vec<String>),
+}
+
+impl ProviderSelection {
+    pub fn names(&self) -> &[String] {
+        match self {
+            ProviderSelection::Single(name) => std::slice::from_ref(name),
+            ProviderSelection::Chain(names) => names,
+        }
+    }
+}
+
+impl From<String> for ProviderSelection {
+    fn from(name: String) -> Self {
+        ProviderSelection::Single(name)
+    }
+}
+/// provider configs keyed by instance name
+///
+/// an entry may inherit the fields of another entry with `extends: <name>`,
+/// its own fields win over the base entry, which in turn wins over `Config.defaults`
+///
+/// loading keeps each entry as the JSON text it was written as, and only checks `extends` and the
+/// `provider` tags. an entry is merged with what it inherits and deserialized into its
+/// `Box<dyn ProviderConfig>` on first access, and a broken entry is reported then
+#[derive(Clone)]
+pub struct ProviderConfigMap {
+    entries: HashMap<String, LazyEntry>,
+    /// instance name -> base entry name, kept so saving writes back only the overridden fields
+    extends: HashMap<String, String>,
+    /// the `defaults` the entries were loaded with
+    defaults: Arc<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl Serialize for ProviderConfigMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_raw(ProviderTag::default(), &self.defaults, Output::Values).map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProviderConfigMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (mut map, _) = ProviderConfigMap::from_entries(RawEntries::deserialize(deserializer)?.0);
+        map.resolve(ProviderTag::default()).map_err(D::Error::custom)?;
+        Ok(map)
+    }
 }
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ProviderConfigMap(HashMap<String, Box<dyn ProviderConfig>>);
 
 
 impl Debug for ProviderConfigMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ProviderConfigMap")
-            .field("provider_config", &self.0.keys())
+            .field("provider_config", &self.entries.keys())
             .finish()
     }
 }
 
 impl ProviderConfigMap {
-    pub fn get(&self, provider: &str) -> Option<&Box<dyn ProviderConfig>> {
-        self.0.get(provider)
+    /// an empty map, `default()` holds one entry per provider kind
+    pub fn new() -> Self {
+        ProviderConfigMap { entries: HashMap::new(), extends: HashMap::new(), defaults: Arc::default() }
+    }
+
+    /// get the provider config `provider`, `None` when there is none or it fails to deserialize
+    pub fn get(&self, provider: &str) -> Option<&(dyn ProviderConfig + 'static)> {
+        match self.try_get(provider) {
+            Ok(pcfg) => pcfg,
+            Err(e) => {
+                debug!("{}", e);
+                None
+            }
+        }
     }
 
+    /// get the provider config `provider`, deserializing it on first access
+    pub fn try_get(&self, provider: &str) -> anyhow::Result<Option<&(dyn ProviderConfig + 'static)>> {
+        let Some(entry) = self.entries.get(provider) else {
+            return Ok(None);
+        };
+        let parsed = entry.parsed.get_or_init(|| {
+            self.fields(provider)
+                .and_then(|fields| Ok(Box::<dyn ProviderConfig>::deserialize(serde_json::Value::Object(fields))?))
+                .map_err(|e| format!("invalid provider config {}: {}", provider, e))
+        });
+        parsed.as_deref().map(Some).map_err(|e| anyhow::format_err!("{}", e))
+    }
+
+    /// the templates the entry was loaded with are kept for the fields `pcfg` does not change
     pub fn set(&mut self, provider: &str, pcfg: Box<dyn ProviderConfig>) {
-        self.0.insert(provider.to_string(), pcfg);
+        let entry = match self.entries.remove(provider) {
+            Some(loaded) => LazyEntry { parsed: OnceLock::from(Ok(pcfg)), ..loaded },
+            None => LazyEntry::parsed(pcfg),
+        };
+        self.entries.insert(provider.to_string(), entry);
+    }
+
+    /// remove the provider config `provider`, entries extending it keep the fields they inherited
+    pub fn remove(&mut self, provider: &str) -> Option<Box<dyn ProviderConfig>> {
+        let removed = self.try_get(provider).ok()?.map(dyn_clone::clone_box);
+        let dependents: Vec<String> = self.extends.iter()
+            .filter(|(_, base)| *base == provider)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in dependents {
+            // the fields passed on by the base become the entry's own
+            if let Ok(fields) = self.fields(&name) {
+                if let (Some(entry), Ok(loaded)) = (self.entries.get(&name), LazyEntry::loaded(fields.into(), None)) {
+                    let kind = entry.kind.clone();
+                    self.entries.insert(name.clone(), LazyEntry { kind, ..loaded });
+                }
+            }
+            self.extends.remove(&name);
+        }
+        self.entries.remove(provider);
+        self.extends.remove(provider);
+        removed
+    }
+
+    /// the names of the provider configs deserialized so far, the only ones `set` can have changed
+    pub(crate) fn loaded_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter()
+            .filter(|(_, entry)| entry.parsed.get().is_some())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// all provider instance names, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    /// the kind of the provider config `provider`, read without deserializing it
+    pub fn kind(&self, provider: &str) -> Option<&str> {
+        self.entries.get(provider)?.kind()
+    }
+
+    /// the entry `provider` inherits from
+    pub fn extends(&self, provider: &str) -> Option<&str> {
+        self.extends.get(provider).map(|base| base.as_str())
+    }
+
+    /// the map of the entries as written, and the names of those holding a `${`,
+    /// nothing is checked before `resolve`
+    fn from_entries(raw: Vec<(String, RawEntry)>) -> (Self, Vec<String>) {
+        let mut entries = HashMap::with_capacity(raw.len());
+        let mut extends = HashMap::new();
+        let mut templated = Vec::new();
+        for (name, entry) in raw {
+            if let Some(base) = entry.extends {
+                extends.insert(name.clone(), base);
+            }
+            if entry.templated {
+                templated.push(name.clone());
+            }
+            entries.insert(name, LazyEntry::written(entry.json, entry.provider));
+        }
+        (ProviderConfigMap { entries, extends, defaults: Arc::default() }, templated)
+    }
+
+    /// give entry `name` its interpolated `value`, which may name another tag or base
+    fn set_expanded(&mut self, name: &str, value: serde_json::Value) -> anyhow::Result<()> {
+        match value.get("extends") {
+            Some(serde_json::Value::String(base)) => {
+                self.extends.insert(name.to_string(), base.clone());
+            }
+            Some(_) => anyhow::bail!("provider config {}: extends must be an entry name", name),
+            None => {}
+        }
+        let kind = value.get("provider").and_then(|kind| kind.as_str()).map(|kind| kind.to_string());
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.expanded = Some(serde_json::value::to_raw_value(&value)?);
+            entry.kind = kind;
+        }
+        Ok(())
+    }
+
+    /// check every `extends` names an entry without a cycle, and give each entry its `provider` tag,
+    /// inferred from the key where `tag` allows it or inherited along `extends`, checked against the key
+    fn resolve(&mut self, tag: ProviderTag) -> anyhow::Result<()> {
+        for (name, base) in &self.extends {
+            if !self.entries.contains_key(base) {
+                anyhow::bail!("provider config {} extends unknown entry {}", name, base);
+            }
+        }
+        if tag == ProviderTag::Inferred {
+            for (name, entry) in self.entries.iter_mut() {
+                if entry.kind.is_none() && is_provider_kind(name) {
+                    entry.kind = Some(name.clone());
+                }
+            }
+        }
+
+        let mut inherited = Vec::new();
+        for name in self.extends.keys() {
+            let mut chain = vec![name.as_str()];
+            while let Some(base) = self.extends.get(chain[chain.len() - 1]) {
+                if chain.contains(&base.as_str()) {
+                    anyhow::bail!("provider config extends cycle: {} -> {}", chain.join(" -> "), base);
+                }
+                chain.push(base);
+            }
+            if self.entries[name].kind.is_none() {
+                inherited.push((name.clone(), chain.iter().find_map(|name| self.entries[*name].kind.clone())));
+            }
+        }
+        for (name, kind) in inherited {
+            if let Some(entry) = self.entries.get_mut(&name) {
+                entry.kind = kind;
+            }
+        }
+
+        let default_kind = self.defaults.get("provider").and_then(|kind| kind.as_str());
+        for (name, entry) in self.entries.iter_mut() {
+            if entry.kind.is_none() {
+                entry.kind = default_kind.map(|kind| kind.to_string());
+            }
+            check_provider_tag(name, entry.kind.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// the fields entry `name` deserializes from: its own over those of the entry it extends,
+    /// over `defaults`, with its `provider` tag
+    fn fields(&self, name: &str) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+        let Some(entry) = self.entries.get(name) else {
+            return Ok(serde_json::Map::new());
+        };
+        if let Some(Ok(pcfg)) = entry.parsed.get() {
+            return match serde_json::to_value(pcfg)? {
+                serde_json::Value::Object(fields) => Ok(fields),
+                _ => anyhow::bail!("provider config {} is not a map", name),
+            };
+        }
+        let mut fields = match self.extends.get(name) {
+            // `resolve` rejected cycles
+            Some(base) => self.fields(base)?,
+            None => (*self.defaults).clone(),
+        };
+        match entry.own_value()? {
+            serde_json::Value::Object(own) => fields.extend(own),
+            serde_json::Value::Null => {}
+            _ => anyhow::bail!("provider config {} is not a map", name),
+        }
+        fields.remove("extends");
+        if let Some(kind) = &entry.kind {
+            fields.insert("provider".to_string(), kind.as_str().into());
+        }
+        Ok(fields)
+    }
+
+    /// the fields entry `name` sets itself with its `provider` tag, all of them once it is deserialized
+    fn own_fields(&self, entry: &LazyEntry) -> anyhow::Result<serde_json::Value> {
+        let mut value = entry.to_value()?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("extends");
+            if let Some(kind) = entry.kind() {
+                fields.entry("provider").or_insert_with(|| kind.into());
+            }
+        }
+        Ok(value)
+    }
+
+    /// untyped entries for saving, without the fields inherited from `extends` or `defaults`,
+    /// and with the `provider` tag left out where `tag` allows it
+    fn to_raw(
+        &self,
+        tag: ProviderTag,
+        defaults: &serde_json::Map<String, serde_json::Value>,
+        output: Output,
+    ) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+        let mut raw = serde_json::Map::with_capacity(self.entries.len());
+        for (name, lazy) in &self.entries {
+            let mut value = self.own_fields(lazy)?;
+            let Some(entry) = value.as_object_mut() else {
+                raw.insert(name.clone(), value);
+                continue;
+            };
+            let base = self.extends.get(name).filter(|base| self.entries.contains_key(*base));
+            let inherited = match base {
+                Some(base) => Cow::Owned(self.fields(base)?),
+                None => Cow::Borrowed(defaults),
+            };
+            entry.retain(|field, value| inherited.get(field) != Some(value));
+            if let Some(base) = base {
+                entry.insert("extends".to_string(), base.as_str().into());
+            }
+            if tag == ProviderTag::Inferred && Some(name.as_str()) == lazy.kind() {
+                entry.remove("provider");
+            }
+            match (output, &lazy.expanded) {
+                (Output::Values, _) => {}
+                (Output::Templates, Some(expanded)) => {
+                    let expanded = serde_json::from_str(expanded.get())?;
+                    restore_templates(&mut value, &lazy.written_value()?, &expanded);
+                }
+                _ => escape(&mut value),
+            }
+            raw.insert(name.clone(), value);
+        }
+        Ok(raw)
+    }
+}
+
+/// a map key naming a provider kind must agree with the `provider` tag,
+/// other keys are instance names and need the tag to tell their kind
+fn check_provider_tag(name: &str, kind: Option<&str>) -> anyhow::Result<()> {
+    match kind {
+        Some(kind) if is_provider_kind(name) && kind != name => {
+            anyhow::bail!("provider config key {} does not match its provider tag {}", name, kind);
+        }
+        Some(_) => Ok(()),
+        None => anyhow::bail!("provider config {} has no provider tag", name),
+    }
+}
+
+/// a provider config kept as the JSON text it was written as until first accessed
+#[derive(Clone)]
+struct LazyEntry {
+    /// the entry's own fields, `extends` included, `None` for entries set in code
+    written: Option<Box<RawValue>>,
+    /// `written` after interpolation, when that changed anything
+    expanded: Option<Box<RawValue>>,
+    /// the `provider` tag, its own, inferred from the key or inherited
+    kind: Option<String>,
+    parsed: OnceLock<Result<Box<dyn ProviderConfig>, String>>,
+}
+
+impl LazyEntry {
+    fn written(json: Box<RawValue>, kind: Option<String>) -> Self {
+        LazyEntry { written: Some(json), expanded: None, kind, parsed: OnceLock::new() }
+    }
+
+    /// an entry holding the expanded `value`, written with its `${` escaped
+    fn loaded(value: serde_json::Value, kind: Option<String>) -> anyhow::Result<Self> {
+        let mut written = value.clone();
+        escape(&mut written);
+        let expanded = if written != value { Some(serde_json::value::to_raw_value(&value)?) } else { None };
+        Ok(LazyEntry { expanded, ..LazyEntry::written(serde_json::value::to_raw_value(&written)?, kind) })
+    }
+
+    fn parsed(pcfg: Box<dyn ProviderConfig>) -> Self {
+        LazyEntry { written: None, expanded: None, kind: None, parsed: OnceLock::from(Ok(pcfg)) }
+    }
+
+    /// the fields as written, templates and all
+    fn written_value(&self) -> anyhow::Result<serde_json::Value> {
+        match &self.written {
+            Some(json) => Ok(serde_json::from_str(json.get())?),
+            None => Ok(serde_json::Value::Null),
+        }
+    }
+
+    /// the fields as expanded
+    fn own_value(&self) -> anyhow::Result<serde_json::Value> {
+        match &self.expanded {
+            Some(json) => Ok(serde_json::from_str(json.get())?),
+            None => self.written_value(),
+        }
+    }
+
+    /// the value to save, an entry never accessed or failing to deserialize is saved as written
+    fn to_value(&self) -> anyhow::Result<serde_json::Value> {
+        match self.parsed.get() {
+            Some(Ok(pcfg)) => Ok(serde_json::to_value(pcfg)?),
+            _ => self.own_value(),
+        }
+    }
+
+    fn kind(&self) -> Option<&str> {
+        match self.parsed.get() {
+            Some(Ok(pcfg)) => Some(pcfg.kind()),
+            _ => self.kind.as_deref(),
+        }
     }
 }
 
 impl Default for ProviderConfigMap {
     fn default() -> Self {
-        let mut provider_config: HashMap<String, Box<dyn ProviderConfig>> = HashMap::new();
+        let mut provider_config: HashMap<String, LazyEntry> = HashMap::new();
         provider_config.insert(
             "OpenaiMobile".to_string(),
-            Box::new(OpenaiMobile::default()),
+            LazyEntry::parsed(Box::new(OpenaiMobile::default())),
         );
         provider_config.insert(
             "Claude2".to_string(),
-            Box::new(Claude2::default()),
+            LazyEntry::parsed(Box::new(Claude2::default())),
         );
-        ProviderConfigMap(provider_config)
+        ProviderConfigMap { entries: provider_config, extends: HashMap::new(), defaults: Arc::default() }
     }
 }
 
 #[typetag::serde(tag = "provider")]
-pub trait ProviderConfig: DynClone + Send + Sync {
-    fn api_base(&self) -> String;
-    fn token(&self) -> String;
-    fn set_token(&mut self, token: String);
-    fn cookies(&self) -> Option<HashMap<String, String>>;
-    fn model(&self) -> Option<String>;
+pub trait ProviderConfig: ProviderAccess + DynClone + Send + Sync {
+    /// the provider kind, the map key is the instance name
+    fn kind(&self) -> &'static str {
+        self.typetag_name()
+    }
+    fn as_any(&self) -> &dyn Any;
+    /// JSON schema of this kind's fields
+    fn schema(&self) -> serde_json::Value;
 }
 dyn_clone::clone_trait_object!(ProviderConfig);
 
+/// only the kind, so logging a provider config never prints its token or cookies
+impl Debug for dyn ProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.kind())
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         let provider_config  = ProviderConfigMap::default();
         Config {
-            provider: Some("OpenaiMobile".to_string()),
+            provider: Some("OpenaiMobile".to_string().into()),
+            fallback: Vec::new(),
+            provider_tag: ProviderTag::default(),
+            defaults: serde_json::Map::new(),
             provider_config,
+            source: None,
+            templates: Templates::default(),
         }
     }
 }
@@ -69,18 +671,66 @@ impl Default for Config {
 impl Config {
 
     /// get current provider config
-    pub fn current_provider_config(&self) -> Option<&Box<dyn ProviderConfig>> {
-        self.provider_config.get(self.provider().as_str())
+    pub fn current_provider_config(&self) -> Option<&(dyn ProviderConfig + 'static)> {
+        self.provider_config.get(self.first_provider())
+    }
+
+    /// get current provider config, with the error when it fails to deserialize
+    pub fn try_current_provider_config(&self) -> anyhow::Result<&(dyn ProviderConfig + 'static)> {
+        let provider = self.first_provider();
+        self.provider_config.try_get(provider)?
+            .ok_or_else(|| anyhow::format_err!("no provider config {}", provider))
     }
 
+    /// the first provider of the chain
     pub fn provider(&self) -> String {
-        self.provider.clone().unwrap_or_else(|| "OpenaiMobile".to_string())
+        self.first_provider().to_string()
+    }
+
+    /// the first of `providers()`, without building the chain
+    fn first_provider(&self) -> &str {
+        let selected = self.provider.as_ref().map(|p| p.names()).unwrap_or_default();
+        selected.first().or(self.fallback.first()).map_or("OpenaiMobile", |name| name.as_str())
+    }
+
+    /// all configured providers in the order they should be tried, `provider` first, then `fallback`
+    pub fn providers(&self) -> Vec<String> {
+        let mut providers: Vec<String> = Vec::new();
+        let selected = self.provider.as_ref().map(|p| p.names()).unwrap_or_default();
+        for name in selected.iter().chain(self.fallback.iter()) {
+            if !providers.contains(name) {
+                providers.push(name.clone());
+            }
+        }
+        if providers.is_empty() {
+            providers.push("OpenaiMobile".to_string());
+        }
+        providers
+    }
+
+    /// iterate the provider chain in order, skipping missing entries and entries failing validation
+    pub fn provider_chain(&self) -> impl Iterator<Item = (String, &dyn ProviderConfig)> + '_ {
+        self.providers().into_iter().filter_map(move |name| {
+            let Some(pcfg) = self.provider_config.get(&name) else {
+                debug!("skip provider {}: no provider config", name);
+                return None;
+            };
+            if let Err(e) = pcfg.validate() {
+                debug!("skip provider {}: {}", name, e);
+                return None;
+            }
+            Some((name, pcfg))
+        })
     }
 
     pub fn from_str() -> anyhow::Result<Self> {
         let contents: Vec<u8> = r#"
 provider: OpenaiMobile
-#provider: Claude2
+#provider: [openai-personal, OpenaiMobile]
+fallback:
+  - Claude2
+defaults:
+  timeout: 30
 provider_config:
   Claude2:
     provider: "Claude2"
@@ -96,8 +746,13 @@ provider_config:
     cookies:
         cookie_foo: "foo_value"
         cookie_bar: "bar_value"
+  openai-personal:
+    extends: OpenaiMobile
+    api_base: "${provider_config.OpenaiMobile.api_base}/v1"
+    token: "tk-xxxxxxxx-002"
+    cookies: {}
 "#.into();
-         let config: Config = serde_yaml::from_str(&String::from_utf8_lossy(contents.as_slice())).unwrap();
+        let config: Config = serde_yaml::from_str(&String::from_utf8_lossy(contents.as_slice())).unwrap();
 
         debug!("read config success: {:?}", config);
 
@@ -105,64 +760,280 @@ provider_config:
     }
 
     pub fn to_string(&self) -> anyhow::Result<String> {
-        Ok(serde_yaml::to_string(self).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))?)
+        serde_yaml::to_string(&self.to_document()?).map_err(|e| anyhow::format_err!("serde config to yaml failed: {}", e))
     }
 }
 
+impl ConfigStore for Config {
+    fn provider(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.first_provider())
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claude2 {
-    pub cookies: HashMap<String, String>,
-    pub api_base: Option<String>,
-}
+    fn providers(&self) -> Vec<String> {
+        Config::providers(self)
+    }
 
-impl Default for Claude2 {
-    fn default() -> Self {
-        Claude2 {
-            cookies: HashMap::new(),
-            api_base: "https://claude.io".to_string().into(),
+    fn provider_config(&self, provider: &str) -> Option<ProviderView<'_>> {
+        let (name, _) = self.provider_config.entries.get_key_value(provider)?;
+        let pcfg = self.provider_config.get(provider)?;
+        Some(ProviderView {
+            name: Cow::Borrowed(name),
+            kind: Cow::Borrowed(pcfg.kind()),
+            api_base: Some(Cow::Owned(pcfg.api_base())),
+            token: Some(pcfg.token()).filter(|token| !token.is_empty()).map(Cow::Owned),
+            model: pcfg.model().map(Cow::Owned),
+            cookies: Cow::Owned(pcfg.cookies().unwrap_or_default()),
+            timeout: pcfg.timeout().map(|timeout| timeout.as_secs()),
+            expires_at: pcfg.expires_at(),
+        }.normalized())
+    }
+
+    fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.provider_config.names().map(|name| name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// the active provider replaces the whole `provider` chain, `fallback` is kept
+    fn set_active(&mut self, provider: &str) -> anyhow::Result<()> {
+        if self.provider_config.get(provider).is_none() {
+            anyhow::bail!("no provider config {}", provider);
         }
+        let old = self.provider();
+        self.provider = Some(provider.to_string().into());
+        if old != provider {
+            AuditEvent::provider_switch(&old, provider).emit();
+        }
+        Ok(())
+    }
+
+    fn to_string(&self) -> anyhow::Result<String> {
+        self.to_string()
+    }
+
+    fn from_yaml(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
     }
 }
 
-#[typetag::serde]
-impl ProviderConfig for Claude2 {
-    fn api_base(&self) -> String {
-        self.api_base.clone().unwrap_or_else(|| "https://claude.io".to_string())
+/// write a provider config, `provider` tag included, to any serializer behind `erased_serde`,
+/// so plugins built against another serde format crate can exchange provider configs
+pub fn serialize_provider(pcfg: &dyn ProviderConfig, serializer: &mut dyn erased_serde::Serializer) -> anyhow::Result<()> {
+    pcfg.serialize(serializer)
+        .map_err(|e| anyhow::format_err!("serialize provider config {}: {}", pcfg.kind(), e))?;
+    Ok(())
+}
+
+/// read the provider config `name` written by [`serialize_provider`],
+/// like a map key, a name that is a provider kind must match the `provider` tag
+pub fn deserialize_provider<'de>(name: &str, deserializer: &mut dyn erased_serde::Deserializer<'de>) -> anyhow::Result<Box<dyn ProviderConfig>> {
+    let pcfg = Box::<dyn ProviderConfig>::deserialize(deserializer)
+        .map_err(|e| anyhow::format_err!("invalid provider config {}: {}", name, e))?;
+    if is_provider_kind(name) && pcfg.kind() != name {
+        anyhow::bail!("provider config key {} does not match its provider tag {}", name, pcfg.kind());
     }
+    debug!("deserialized provider config {} of kind {}", name, pcfg.kind());
+    Ok(pcfg)
+}
+
+/// `Box<dyn ProviderConfig>` as a `(kind, fields)` pair, for `#[serde(with = "externally_tagged")]`
+///
+/// unlike typetag's map with a `provider` entry this is a fixed layout, which needs nothing
+/// self-describing from the format. the fields are the well known ones of `ProviderAccess`,
+/// any other field, e.g. one inherited from `defaults`, is carried as JSON text
+pub mod externally_tagged {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use super::{ProviderConfig, WireFields};
 
-    fn token(&self) -> String {
-        "".to_string()
+    // `#[serde(with)]` passes a reference to the field
+    #[allow(clippy::borrowed_box)]
+    pub fn serialize<S: Serializer>(pcfg: &Box<dyn ProviderConfig>, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = serde_json::to_value(pcfg).map_err(S::Error::custom)?;
+        WireFields::from_value(&value).map_err(S::Error::custom)?.serialize(serializer)
     }
 
-    fn set_token(&mut self, _token: String) {
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<dyn ProviderConfig>, D::Error> {
+        let (kind, fields) = <(String, WireFields)>::deserialize(deserializer)?;
+        let value = serde_json::Value::Object(fields.into_entry(kind).map_err(D::Error::custom)?);
+        Box::<dyn ProviderConfig>::deserialize(&value).map_err(D::Error::custom)
     }
-    fn cookies(&self) -> Option<HashMap<String, String>> {
-        self.cookies.clone().into()
+}
+
+/// every well known provider field in a fixed order, absent ones as `None`,
+/// then the remaining fields as JSON text
+#[derive(Serialize, Deserialize)]
+struct WireFields {
+    api_base: Option<String>,
+    token: Option<String>,
+    model: Option<String>,
+    cookies: Option<HashMap<String, String>>,
+    timeout: Option<u64>,
+    expires_at: Option<DateTime<Utc>>,
+    other: String,
+}
+
+impl WireFields {
+    /// split the JSON form of a provider config into its kind and fields
+    fn from_value(value: &serde_json::Value) -> anyhow::Result<(String, Self)> {
+        let mut entry = value.as_object().cloned()
+            .ok_or_else(|| anyhow::format_err!("provider config is not a map"))?;
+        let kind = match entry.remove("provider") {
+            Some(serde_json::Value::String(kind)) => kind,
+            _ => anyhow::bail!("provider config has no provider tag"),
+        };
+        let fields = WireFields {
+            api_base: take_field(&mut entry, "api_base"),
+            token: take_field(&mut entry, "token"),
+            model: take_field(&mut entry, "model"),
+            cookies: take_field(&mut entry, "cookies"),
+            timeout: take_field(&mut entry, "timeout"),
+            expires_at: take_field(&mut entry, "expires_at"),
+            other: serde_json::to_string(&entry)?,
+        };
+        Ok((kind, fields))
     }
 
-    fn model(&self) -> Option<String> {
-        Some("claude2".to_string())
+    fn into_entry(self, kind: String) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+        let mut entry = serde_json::Map::new();
+        entry.insert("provider".to_string(), kind.into());
+        if let Some(api_base) = self.api_base {
+            entry.insert("api_base".to_string(), api_base.into());
+        }
+        if let Some(token) = self.token {
+            entry.insert("token".to_string(), token.into());
+        }
+        if let Some(model) = self.model {
+            entry.insert("model".to_string(), model.into());
+        }
+        if let Some(cookies) = self.cookies {
+            let cookies = cookies.into_iter().map(|(k, v)| (k, v.into())).collect();
+            entry.insert("cookies".to_string(), serde_json::Value::Object(cookies));
+        }
+        if let Some(timeout) = self.timeout {
+            entry.insert("timeout".to_string(), timeout.into());
+        }
+        if let Some(expires_at) = self.expires_at {
+            entry.insert("expires_at".to_string(), serde_json::json!(expires_at));
+        }
+        let other: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&self.other)?;
+        entry.extend(other);
+        Ok(entry)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OpenaiMobile {
-    pub token: Option<String>,
-    pub api_base: Option<String>,
-    pub model: Option<String>,
-    pub cookies: HashMap<String, String>,
+/// remove the well known field `key` from `entry` if it reads back as the same value typed as `T`,
+/// anything else, e.g. a `null` or a differently formatted date, stays among the other fields
+fn take_field<T: serde::de::DeserializeOwned + Serialize>(entry: &mut serde_json::Map<String, serde_json::Value>, key: &str) -> Option<T> {
+    let value = entry.get(key)?;
+    let typed = T::deserialize(value).ok()?;
+    if serde_json::to_value(&typed).ok().as_ref() != Some(value) {
+        return None;
+    }
+    entry.remove(key);
+    Some(typed)
 }
 
-impl Default for OpenaiMobile {
-    fn default() -> Self {
-        OpenaiMobile {
-            token: None,
-            api_base: "https://api.openai.com".to_string().into(),
-            model: "davinci".to_string().into(),
-            cookies: HashMap::new(),
+/// the binary shape of `Config`, which otherwise loads through `serde_json::Value` and an untagged enum,
+/// nothing in it needs a self-describing format
+#[cfg(feature = "binary")]
+#[derive(Serialize, Deserialize)]
+struct WireConfig {
+    provider: Option<WireSelection>,
+    fallback: Vec<String>,
+    provider_tag: ProviderTag,
+    /// JSON text, defaults may hold any value
+    defaults: String,
+    extends: BTreeMap<String, String>,
+    provider_config: BTreeMap<String, (String, WireFields)>,
+}
+
+/// `ProviderSelection` without `#[serde(untagged)]`
+#[cfg(feature = "binary")]
+#[derive(Serialize, Deserialize)]
+enum WireSelection {
+    Single(String),
+    Chain(Vec<String>),
+}
+
+#[cfg(feature = "binary")]
+impl Config {
+    fn to_wire(&self) -> anyhow::Result<WireConfig> {
+        let mut provider_config = BTreeMap::new();
+        for (name, entry) in &self.provider_config.entries {
+            let fields = WireFields::from_value(&self.provider_config.own_fields(entry)?)
+                .map_err(|e| anyhow::format_err!("provider config {}: {}", name, e))?;
+            provider_config.insert(name.clone(), fields);
         }
+        Ok(WireConfig {
+            provider: self.provider.clone().map(|provider| match provider {
+                ProviderSelection::Single(name) => WireSelection::Single(name),
+                ProviderSelection::Chain(names) => WireSelection::Chain(names),
+            }),
+            fallback: self.fallback.clone(),
+            provider_tag: self.provider_tag,
+            defaults: serde_json::to_string(&self.defaults)?,
+            extends: self.provider_config.extends.clone().into_iter().collect(),
+            provider_config,
+        })
     }
+
+    /// the provider configs stay lazy, values were already interpolated when the config was first loaded
+    fn from_wire(wire: WireConfig) -> anyhow::Result<Self> {
+        let mut entries = HashMap::new();
+        for (name, (kind, fields)) in wire.provider_config {
+            check_provider_tag(&name, Some(&kind))?;
+            let entry = fields.into_entry(kind.clone())?;
+            entries.insert(name, LazyEntry::loaded(entry.into(), Some(kind))?);
+        }
+        let defaults: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&wire.defaults)?;
+        Ok(Config {
+            provider: wire.provider.map(|provider| match provider {
+                WireSelection::Single(name) => ProviderSelection::Single(name),
+                WireSelection::Chain(names) => ProviderSelection::Chain(names),
+            }),
+            fallback: wire.fallback,
+            provider_tag: wire.provider_tag,
+            provider_config: ProviderConfigMap { entries, extends: wire.extends.into_iter().collect(), defaults: Arc::new(defaults.clone()) },
+            defaults,
+            source: None,
+            templates: Templates::default(),
+        })
+    }
+
+    pub fn to_bincode(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(bincode::serialize(&self.to_wire()?)?)
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> anyhow::Result<Self> {
+        Config::from_wire(bincode::deserialize(bytes)?)
+    }
+
+    pub fn to_cbor(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&self.to_wire()?, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> anyhow::Result<Self> {
+        Config::from_wire(ciborium::from_reader(bytes)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ProviderConfig)]
+#[provider(model = "claude2")]
+pub struct Claude2 {
+    #[provider(required)]
+    pub cookies: HashMap<String, String>,
+    #[provider(default = "https://claude.io")]
+    pub api_base: Option<String>,
+    /// request timeout in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// when the session cookies expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 /// we need the tag name to be snake_case, but currently typetag not support this,
@@ -173,24 +1044,19 @@ impl Default for OpenaiMobile {
 /// `#[typetag::serde(name = "openai_mobile")]`
 /// because this only effect the trait `type` attribute
 /// our HashMap key still use the type name `OpenaiMobile`, not `openai_mobile`
-#[typetag::serde]
-impl ProviderConfig for OpenaiMobile {
-    fn api_base(&self) -> String {
-        return self.api_base.clone().unwrap_or_else(|| "https://api.openai.com".to_string());
-    }
-
-    fn token(&self) -> String {
-        return self.token.clone().unwrap_or_else(|| "".to_string());
-    }
-
-    fn set_token(&mut self, token: String) {
-        self.token = Some(token);
-    }
-    fn cookies(&self) -> Option<HashMap<String, String>> {
-        return self.cookies.clone().into();
-    }
-
-    fn model(&self) -> Option<String> {
-        self.model.clone().unwrap_or_else(|| "davinci".to_string()).into()
-    }
+#[derive(Debug, Clone, Serialize, Deserialize, ProviderConfig)]
+pub struct OpenaiMobile {
+    #[provider(required)]
+    pub token: Option<String>,
+    #[provider(default = "https://api.openai.com")]
+    pub api_base: Option<String>,
+    #[provider(default = "davinci")]
+    pub model: Option<String>,
+    pub cookies: HashMap<String, String>,
+    /// request timeout in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// when the token expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
 }
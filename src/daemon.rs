@@ -0,0 +1,232 @@
+//! a config daemon owning one `config_trait_object::Config`, so worker processes ask it
+//! instead of each parsing the YAML, and the matching client
+//!
+//! requests and responses are JSON frames of [`JsonFrameCodec`] over TCP or a Unix socket,
+//! one request is answered by one response, except `Subscribe`, after which the connection
+//! only carries the config, once right away and again on every change
+//!
+//! there is no authentication: anyone who can connect reads every token and can replace them
+//! with `SetToken`, so listen on a loopback address or a Unix socket only others can't open,
+//! [`bind_tcp`] refuses anything else
+
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
+use futures::{SinkExt, Stream, StreamExt};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs, UnixListener, UnixStream};
+use tokio_util::codec::Framed;
+use crate::codec::JsonFrameCodec;
+use crate::config_trait_object::{Config, ProviderConfig};
+use crate::shared::SharedConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    CurrentProvider,
+    GetEntry { name: String },
+    SetToken { name: String, token: String },
+    Subscribe,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Provider { name: String, config: Box<dyn ProviderConfig> },
+    Entry(Option<Box<dyn ProviderConfig>>),
+    Ok,
+    Error(String),
+    Config(Box<Config>),
+}
+
+/// serves one config to any number of connections, clones share the config
+#[derive(Clone)]
+pub struct Daemon {
+    config: SharedConfig,
+}
+
+impl Daemon {
+    pub fn new(config: Config) -> Self {
+        Daemon::with_shared(SharedConfig::new(config))
+    }
+
+    /// serve a config that is also changed elsewhere, e.g. by the admin API
+    pub fn with_shared(config: SharedConfig) -> Self {
+        Daemon { config }
+    }
+
+    /// a snapshot of the served config
+    pub fn config(&self) -> Arc<Config> {
+        self.config.snapshot()
+    }
+
+    pub async fn serve_tcp(self, listener: TcpListener) -> anyhow::Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            debug!("config daemon: connection from {}", addr);
+            self.spawn(stream);
+        }
+    }
+
+    pub async fn serve_unix(self, listener: UnixListener) -> anyhow::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            debug!("config daemon: connection on unix socket");
+            self.spawn(stream);
+        }
+    }
+
+    fn spawn<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(&self, stream: S) {
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = daemon.handle(stream).await {
+                debug!("config daemon: connection closed: {}", e);
+            }
+        });
+    }
+
+    /// answer the requests of one connection until it is closed
+    pub async fn handle<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> anyhow::Result<()> {
+        let mut framed = Framed::new(stream, JsonFrameCodec::<Request>::new());
+        while let Some(request) = framed.next().await {
+            let response = match request? {
+                Request::Subscribe => return self.subscription(framed).await,
+                request => self.respond(request),
+            };
+            framed.send(&response).await?;
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        debug!("config daemon: {:?}", request);
+        match request {
+            Request::CurrentProvider => {
+                let config = self.config.snapshot();
+                match config.try_current_provider_config() {
+                    Ok(pcfg) => Response::Provider { name: config.provider(), config: dyn_clone::clone_box(pcfg) },
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            Request::GetEntry { name } => match self.config.snapshot().provider_config.try_get(&name) {
+                Ok(pcfg) => Response::Entry(pcfg.map(dyn_clone::clone_box)),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Request::SetToken { name, token } => {
+                let result = self.config.update(|config| {
+                    let pcfg = config.provider_config.try_get(&name)?
+                        .ok_or_else(|| anyhow::format_err!("no provider config {}", name))?;
+                    let mut pcfg = dyn_clone::clone_box(pcfg);
+                    pcfg.set_token(token);
+                    config.provider_config.set(&name, pcfg);
+                    Ok(())
+                });
+                match result {
+                    Ok(()) => Response::Ok,
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            Request::Subscribe => Response::Error("subscribe is handled by the connection".to_string()),
+        }
+    }
+
+    async fn subscription<S: AsyncRead + AsyncWrite + Unpin>(&self, mut framed: Framed<S, JsonFrameCodec<Request>>) -> anyhow::Result<()> {
+        let mut changes = self.config.subscribe();
+        loop {
+            let config = Box::new(Config::clone(&changes.borrow_and_update()));
+            framed.send(&Response::Config(config)).await?;
+            tokio::select! {
+                changed = changes.changed() => changed?,
+                // the subscriber hung up, or sent something it shouldn't have
+                _ = framed.next() => return Ok(()),
+            }
+        }
+    }
+}
+
+/// listen on `addr`, which must be a loopback address, see the module docs
+pub async fn bind_tcp(addr: SocketAddr) -> anyhow::Result<TcpListener> {
+    if !addr.ip().is_loopback() {
+        anyhow::bail!("refusing to listen on {}: the config daemon has no authentication, use a loopback address", addr);
+    }
+    Ok(TcpListener::bind(addr).await?)
+}
+
+/// listen on the Unix socket `path`, replacing a socket left by an earlier run,
+/// anything else at `path` is an error and left alone
+pub fn bind_unix<P: AsRef<Path>>(path: P) -> anyhow::Result<UnixListener> {
+    let path = path.as_ref();
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(UnixListener::bind(path)?)
+}
+
+/// a connection to a [`Daemon`]
+pub struct Client<S> {
+    framed: Framed<S, JsonFrameCodec<Response>>,
+}
+
+impl Client<TcpStream> {
+    pub async fn connect_tcp<A: ToSocketAddrs>(addr: A) -> anyhow::Result<Self> {
+        Ok(Client::new(TcpStream::connect(addr).await?))
+    }
+}
+
+impl Client<UnixStream> {
+    pub async fn connect_unix<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Client::new(UnixStream::connect(path).await?))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
+    pub fn new(stream: S) -> Self {
+        Client { framed: Framed::new(stream, JsonFrameCodec::new()) }
+    }
+
+    async fn request(&mut self, request: Request) -> anyhow::Result<Response> {
+        self.framed.send(&request).await?;
+        match self.framed.next().await {
+            Some(Ok(Response::Error(e))) => anyhow::bail!("{}", e),
+            Some(response) => response,
+            None => anyhow::bail!("config daemon closed the connection"),
+        }
+    }
+
+    /// the active provider instance name and its config
+    pub async fn current_provider(&mut self) -> anyhow::Result<(String, Box<dyn ProviderConfig>)> {
+        match self.request(Request::CurrentProvider).await? {
+            Response::Provider { name, config } => Ok((name, config)),
+            response => anyhow::bail!("unexpected response {:?}", response),
+        }
+    }
+
+    pub async fn get_entry(&mut self, name: &str) -> anyhow::Result<Option<Box<dyn ProviderConfig>>> {
+        match self.request(Request::GetEntry { name: name.to_string() }).await? {
+            Response::Entry(pcfg) => Ok(pcfg),
+            response => anyhow::bail!("unexpected response {:?}", response),
+        }
+    }
+
+    pub async fn set_token(&mut self, name: &str, token: &str) -> anyhow::Result<()> {
+        match self.request(Request::SetToken { name: name.to_string(), token: token.to_string() }).await? {
+            Response::Ok => Ok(()),
+            response => anyhow::bail!("unexpected response {:?}", response),
+        }
+    }
+
+    /// the current config, then the config after every change
+    pub async fn subscribe(mut self) -> anyhow::Result<impl Stream<Item = anyhow::Result<Config>>> {
+        self.framed.send(&Request::Subscribe).await?;
+        Ok(self.framed.map(|response| match response? {
+            Response::Config(config) => Ok(*config),
+            response => anyhow::bail!("unexpected response {:?}", response),
+        }))
+    }
+}
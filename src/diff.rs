@@ -0,0 +1,137 @@
+//! structural diff of two configs of any backend, compared through their [`ProviderView`]s
+//!
+//! `token` and cookie values are masked: the diff tells that they changed, not what to
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use serde::Serialize;
+use crate::store::{ConfigStore, ProviderView};
+
+/// what secret values read as in a diff
+pub const MASK: &str = "***";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// the active provider instance
+    Active { from: String, to: String },
+    /// the provider instances tried after the active one
+    Fallback { from: Vec<String>, to: Vec<String> },
+    Added { provider: String, kind: String },
+    Removed { provider: String, kind: String },
+    Kind { provider: String, from: String, to: String },
+    /// a field of a provider config, e.g. `OpenaiMobile.model` or `Claude2.cookies.cookie1`,
+    /// `None` when it is not set, masked when `secret`
+    Field { path: String, from: Option<String>, to: Option<String>, secret: bool },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Active { from, to } => write!(f, "provider: {} -> {}", from, to),
+            Change::Fallback { from, to } => write!(f, "fallback: {} -> {}", list(from), list(to)),
+            Change::Added { provider, kind } => write!(f, "{} added ({})", provider, kind),
+            Change::Removed { provider, kind } => write!(f, "{} removed ({})", provider, kind),
+            Change::Kind { provider, from, to } => write!(f, "{}: kind {} -> {}", provider, from, to),
+            Change::Field { path, from, to, secret: true } => match (from, to) {
+                (None, _) => write!(f, "{} added", path),
+                (_, None) => write!(f, "{} removed", path),
+                _ => write!(f, "{} changed", path),
+            },
+            Change::Field { path, from, to, secret: false } => {
+                let from = from.as_deref().unwrap_or("none");
+                let to = to.as_deref().unwrap_or("none");
+                write!(f, "{}: {} -> {}", path, from, to)
+            }
+        }
+    }
+}
+
+fn list(names: &[String]) -> String {
+    if names.is_empty() { "none".to_string() } else { names.join(", ") }
+}
+
+/// the changes from one config to another, `Display` renders one change per line, `Serialize` a JSON array
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// compare `old` to `new`, provider configs are matched by instance name
+pub fn diff<A: ConfigStore, B: ConfigStore>(old: &A, new: &B) -> Diff {
+    let mut changes = Vec::new();
+    let (from, to) = (old.provider(), new.provider());
+    if from != to {
+        changes.push(Change::Active { from: from.into_owned(), to: to.into_owned() });
+    }
+    let (mut from, mut to) = (old.providers(), new.providers());
+    if from.get(1..) != to.get(1..) {
+        changes.push(Change::Fallback { from: from.split_off(1.min(from.len())), to: to.split_off(1.min(to.len())) });
+    }
+
+    let names: BTreeSet<String> = old.provider_names().into_iter().chain(new.provider_names()).collect();
+    for name in names {
+        match (old.provider_config(&name), new.provider_config(&name)) {
+            (None, Some(view)) => changes.push(Change::Added { provider: name, kind: view.kind.into_owned() }),
+            (Some(view), None) => changes.push(Change::Removed { provider: name, kind: view.kind.into_owned() }),
+            (Some(old), Some(new)) => diff_provider(&name, &old, &new, &mut changes),
+            (None, None) => {}
+        }
+    }
+    Diff { changes }
+}
+
+fn diff_provider(name: &str, old: &ProviderView<'_>, new: &ProviderView<'_>, changes: &mut Vec<Change>) {
+    if old.kind != new.kind {
+        changes.push(Change::Kind { provider: name.to_string(), from: old.kind.to_string(), to: new.kind.to_string() });
+    }
+    let fields = [
+        ("api_base", &old.api_base, &new.api_base, false),
+        ("model", &old.model, &new.model, false),
+        ("token", &old.token, &new.token, true),
+    ];
+    for (field, from, to, secret) in fields {
+        push_field(changes, format!("{}.{}", name, field), from.as_deref(), to.as_deref(), secret);
+    }
+    let timeout = |view: &ProviderView<'_>| view.timeout.map(|timeout| timeout.to_string());
+    push_field(changes, format!("{}.timeout", name), timeout(old).as_deref(), timeout(new).as_deref(), false);
+    let expires_at = |view: &ProviderView<'_>| view.expires_at.map(|expires_at| expires_at.to_rfc3339());
+    push_field(changes, format!("{}.expires_at", name), expires_at(old).as_deref(), expires_at(new).as_deref(), false);
+    let cookies: BTreeSet<&String> = old.cookies.keys().chain(new.cookies.keys()).collect();
+    for cookie in cookies {
+        let path = format!("{}.cookies.{}", name, cookie);
+        push_field(changes, path, cookie_value(&old.cookies, cookie), cookie_value(&new.cookies, cookie), true);
+    }
+}
+
+fn cookie_value<'a>(cookies: &'a HashMap<String, String>, cookie: &str) -> Option<&'a str> {
+    cookies.get(cookie).map(|value| value.as_str())
+}
+
+fn push_field(changes: &mut Vec<Change>, path: String, from: Option<&str>, to: Option<&str>, secret: bool) {
+    if from == to {
+        return;
+    }
+    let show = |value: Option<&str>| value.map(|value| if secret { MASK.to_string() } else { value.to_string() });
+    changes.push(Change::Field { path, from: show(from), to: show(to), secret });
+}
@@ -0,0 +1,150 @@
+//! saved snapshots of a `config_trait_object::Config`, to see who changed what and go back
+//!
+//! the history of `config.yaml` lives in `config.yaml.history/`: one `<id>.yaml` snapshot per save,
+//! `0600` like the config itself, and `index.jsonl` with one [`HistoryEntry`] per line
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config_trait_object::Config;
+use crate::diff::{diff, Change};
+use crate::persist::{lock, Source};
+use crate::store::ConfigStore;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub author: String,
+    /// what changed since the previous snapshot, never secret values
+    pub summary: Vec<String>,
+}
+
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        History { dir: dir.into() }
+    }
+
+    /// the history kept next to the config file `path`
+    pub fn for_config<P: AsRef<Path>>(path: P) -> Self {
+        let mut dir = path.as_ref().as_os_str().to_owned();
+        dir.push(".history");
+        History::new(dir)
+    }
+
+    fn index(&self) -> PathBuf {
+        self.dir.join("index.jsonl")
+    }
+
+    fn snapshot_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.yaml", id))
+    }
+
+    /// all entries, oldest first
+    pub fn list(&self) -> anyhow::Result<Vec<HistoryEntry>> {
+        let contents = match std::fs::read_to_string(self.index()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        contents.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// the config saved as snapshot `id`
+    pub fn load(&self, id: u64) -> anyhow::Result<Config> {
+        let path = self.snapshot_path(id);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::format_err!("no snapshot {} in {}: {}", id, self.dir.display(), e))?;
+        Config::from_yaml(&contents)
+    }
+
+    /// what changed from snapshot `from` to snapshot `to`
+    pub fn diff(&self, from: u64, to: u64) -> anyhow::Result<Vec<String>> {
+        summarize(&self.load(from)?, &self.load(to)?)
+    }
+
+    /// add `config` as the newest snapshot, `note` goes before the summary of changes
+    pub fn record(&self, config: &Config, author: &str, note: Option<&str>) -> anyhow::Result<HistoryEntry> {
+        std::fs::create_dir_all(&self.dir)?;
+        let _lock = lock(&self.index())?;
+
+        let previous = self.list()?.pop();
+        let mut summary: Vec<String> = note.map(|note| note.to_string()).into_iter().collect();
+        match &previous {
+            Some(previous) => summary.extend(summarize(&self.load(previous.id)?, config)?),
+            None => summary.push("first snapshot".to_string()),
+        }
+        let entry = HistoryEntry {
+            id: previous.map_or(1, |previous| previous.id + 1),
+            timestamp: Utc::now(),
+            author: author.to_string(),
+            summary,
+        };
+
+        let mut snapshot = OpenOptions::new().write(true).create_new(true).mode(0o600).open(self.snapshot_path(entry.id))?;
+        snapshot.write_all(ConfigStore::to_string(config)?.as_bytes())?;
+        snapshot.sync_all()?;
+        let mut index = OpenOptions::new().append(true).create(true).mode(0o600).open(self.index())?;
+        writeln!(index, "{}", serde_json::to_string(&entry)?)?;
+        index.sync_all()?;
+
+        debug!("recorded config snapshot {} in {}", entry.id, self.dir.display());
+        Ok(entry)
+    }
+
+    /// save `config` to `path` and record it
+    pub fn commit<P: AsRef<Path>>(&self, config: &mut Config, path: P, author: &str, note: Option<&str>) -> anyhow::Result<HistoryEntry> {
+        config.save(path)?;
+        self.record(config, author, note)
+    }
+
+    /// write snapshot `id` back to `path`, as a new snapshot, the file must not have changed since it was read,
+    /// it is not parsed, so a broken config can be restored over
+    pub fn restore<P: AsRef<Path>>(&self, id: u64, path: P, author: &str) -> anyhow::Result<HistoryEntry> {
+        let path = path.as_ref();
+        let mut snapshot = self.load(id)?;
+        if path.exists() {
+            let contents = std::fs::read(path)
+                .map_err(|e| anyhow::format_err!("read {}: {}", path.display(), e))?;
+            snapshot.source = Some(Source::from_contents(path, &contents));
+        }
+        self.commit(&mut snapshot, path, author, Some(&format!("restore #{}", id)))
+    }
+}
+
+/// the changes from `old` to `new`, secret values masked, see [`diff`](crate::diff::diff)
+pub fn summarize(old: &Config, new: &Config) -> anyhow::Result<Vec<String>> {
+    let old_value = serde_json::to_value(old)?;
+    let new_value = serde_json::to_value(new)?;
+    let mut changes = Vec::new();
+    for key in ["provider", "fallback", "provider_tag", "defaults"] {
+        if old_value.get(key) != new_value.get(key) {
+            changes.push(format!("{}: {} -> {}", key, brief(old_value.get(key)), brief(new_value.get(key))));
+        }
+    }
+    // the selection is already covered by `provider` above
+    changes.extend(diff(old, new).changes.iter()
+        .filter(|change| !matches!(change, Change::Active { .. }))
+        .map(|change| change.to_string()));
+    Ok(changes)
+}
+
+fn brief(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "none".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
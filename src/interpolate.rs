@@ -0,0 +1,189 @@
+use serde_json::Value;
+
+/// expand `${...}` in every string value of `root`
+///
+/// `${NAME}` is looked up with `vars`, e.g. the environment,
+/// `${provider_config.Claude2.api_base}` (any name containing a `.`) refers to another value of the document,
+/// as written before interpolation. `$${` is an escaped, literal `${`.
+/// undefined variables and reference cycles are errors.
+pub fn interpolate(root: &mut Value, vars: &dyn Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+    // expand against the document as written, then write the results back, so it is never copied
+    let references = |name: &str| Ok(lookup(root, name.split('.')).cloned());
+    let expanded = expansions(root, &[], &references, vars)?;
+    apply(root, expanded);
+    Ok(())
+}
+
+/// the expansion of every string value of `value` that interpolation changes, by JSON pointer into `value`
+///
+/// `path` is where `value` sits in the document, for error messages, and `references` reads
+/// the value a dotted name refers to in the document as written
+pub(crate) fn expansions<'v>(
+    value: &'v Value,
+    path: &[&'v str],
+    references: &dyn Fn(&str) -> anyhow::Result<Option<Value>>,
+    vars: &dyn Fn(&str) -> Option<String>,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let mut interpolator = Interpolator { references, vars, resolving: Vec::new() };
+    let mut keys: Vec<Key> = path.iter().copied().map(Key::Field).collect();
+    let mut expanded = Vec::new();
+    interpolator.walk(value, path.len(), &mut keys, &mut expanded)?;
+    Ok(expanded)
+}
+
+/// write the results of [`expansions`] into `root`
+pub(crate) fn apply(root: &mut Value, expanded: Vec<(String, String)>) {
+    for (pointer, value) in expanded {
+        if let Some(target) = root.pointer_mut(&pointer) {
+            *target = Value::String(value);
+        }
+    }
+}
+
+/// the value at the dotted name split into `keys`, array items by index
+pub(crate) fn lookup<'v, 'k>(value: &'v Value, mut keys: impl Iterator<Item = &'k str>) -> Option<&'v Value> {
+    keys.try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// the inverse of [`interpolate`] for literal values: every `${` in a string value of `root` becomes `$${`,
+/// so a document written after loading reads back the same values instead of being expanded again
+pub fn escape(root: &mut Value) {
+    match root {
+        Value::String(s) if s.contains("${") => *s = s.replace("${", "$${"),
+        Value::Array(items) => items.iter_mut().for_each(escape),
+        Value::Object(map) => map.values_mut().for_each(escape),
+        _ => {}
+    }
+}
+
+/// like [`escape`], except that a string still holding the value a template of `written` expanded to
+/// in `expanded` is written as that template again, `written` and `expanded` are the document as loaded
+pub(crate) fn restore_templates(root: &mut Value, written: &Value, expanded: &Value) {
+    match root {
+        Value::String(s) => match (written, expanded) {
+            (Value::String(template), Value::String(value)) if s == value => *s = template.clone(),
+            _ => escape(root),
+        },
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                restore_templates(item, written.get(i).unwrap_or(&Value::Null), expanded.get(i).unwrap_or(&Value::Null));
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                restore_templates(item, written.get(key).unwrap_or(&Value::Null), expanded.get(key).unwrap_or(&Value::Null));
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Interpolator<'a> {
+    references: &'a dyn Fn(&str) -> anyhow::Result<Option<Value>>,
+    vars: &'a dyn Fn(&str) -> Option<String>,
+    /// references being expanded, for cycle detection
+    resolving: Vec<String>,
+}
+
+impl Interpolator<'_> {
+    /// collect the JSON pointer and expansion of every string value containing a `$`,
+    /// the pointer leaves out the first `skip` keys of `path`
+    fn walk<'v>(&mut self, value: &'v Value, skip: usize, path: &mut Vec<Key<'v>>, expanded: &mut Vec<(String, String)>) -> anyhow::Result<()> {
+        match value {
+            Value::String(s) if s.contains('$') => {
+                let dotted: Vec<String> = path.iter().map(|key| key.to_string()).collect();
+                let value = self.expand(s, &dotted.join("."))?;
+                if value != *s {
+                    let pointer: String = dotted[skip..].iter()
+                        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+                        .collect();
+                    expanded.push((pointer, value));
+                }
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Key::Index(i));
+                    self.walk(item, skip, path, expanded)?;
+                    path.pop();
+                }
+            }
+            Value::Object(map) => {
+                for (key, item) in map {
+                    path.push(Key::Field(key));
+                    self.walk(item, skip, path, expanded)?;
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn expand(&mut self, s: &str, path: &str) -> anyhow::Result<String> {
+        if !s.contains('$') {
+            return Ok(s.to_string());
+        }
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            rest = &rest[i..];
+            if let Some(r) = rest.strip_prefix("$${") {
+                out.push_str("${");
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("${") {
+                let end = r.find('}')
+                    .ok_or_else(|| anyhow::format_err!("unterminated ${{ in {}", path))?;
+                out.push_str(&self.lookup(&r[..end], path)?);
+                rest = &r[end + 1..];
+            } else {
+                out.push('$');
+                rest = &rest[1..];
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn lookup(&mut self, name: &str, path: &str) -> anyhow::Result<String> {
+        if !name.contains('.') {
+            return (self.vars)(name)
+                .ok_or_else(|| anyhow::format_err!("undefined variable ${{{}}} in {}", name, path));
+        }
+
+        if self.resolving.iter().any(|n| n == name) {
+            anyhow::bail!("interpolation cycle: {} -> {}", self.resolving.join(" -> "), name);
+        }
+        match (self.references)(name)? {
+            Some(Value::String(s)) => {
+                self.resolving.push(name.to_string());
+                let expanded = self.expand(&s, name);
+                self.resolving.pop();
+                expanded
+            }
+            Some(Value::Number(n)) => Ok(n.to_string()),
+            Some(Value::Bool(b)) => Ok(b.to_string()),
+            Some(_) => anyhow::bail!("${{{}}} in {} is not a string, number or bool", name, path),
+            None => anyhow::bail!("undefined variable ${{{}}} in {}", name, path),
+        }
+    }
+}
+
+/// a step of the path to a value, only turned into text for a value that is expanded
+enum Key<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+impl std::fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Field(key) => f.write_str(key),
+            Key::Index(i) => write!(f, "{}", i),
+        }
+    }
+}
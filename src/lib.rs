@@ -0,0 +1,53 @@
+//! four ways to (de)serialize a map of provider configs with serde,
+//! each backend is selected by a cargo feature, all four enabled by default,
+//! `binary`, `patch` and `admin` are opt-in
+
+// the derives in `provider-config-derive` name this crate by its path, here too
+extern crate self as serde_trait_object_demo;
+
+#[cfg(feature = "trait-object")]
+pub mod config_trait_object;
+#[cfg(feature = "enum")]
+pub mod config_enum;
+#[cfg(feature = "boxed-trait")]
+pub mod config_struct_boxed_trait;
+#[cfg(feature = "struct")]
+pub mod config_struct;
+#[cfg(feature = "trait-object")]
+pub mod shared;
+#[cfg(feature = "trait-object")]
+pub mod transaction;
+#[cfg(feature = "trait-object")]
+pub mod persist;
+#[cfg(feature = "trait-object")]
+pub mod history;
+#[cfg(feature = "patch")]
+pub mod patch;
+#[cfg(feature = "trait-object")]
+pub mod codec;
+#[cfg(feature = "trait-object")]
+pub mod daemon;
+#[cfg(feature = "admin")]
+pub mod admin;
+#[cfg(feature = "trait-object")]
+pub mod audit;
+#[cfg(feature = "trait-object")]
+pub mod refresh;
+#[cfg(feature = "trait-object")]
+mod raw_entry;
+pub mod diff;
+pub mod interpolate;
+pub mod provider;
+pub mod store;
+
+/// used by the code `#[derive(ProviderConfig)]` generates, not a public API
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    pub use chrono;
+    pub use serde_json;
+    #[cfg(feature = "trait-object")]
+    pub use inventory;
+    #[cfg(feature = "trait-object")]
+    pub use typetag;
+}
@@ -1,7 +1,15 @@
-mod config_trait_object;
-mod config_enum;
-mod config_struct_boxed_trait;
-mod config_struct;
+use serde_trait_object_demo::{config_enum, config_struct, config_struct_boxed_trait, config_trait_object};
+use serde_trait_object_demo::provider::ProviderAccess;
+use serde_trait_object_demo::shared::SharedConfig;
+use serde_trait_object_demo::store::ConfigStore;
+
+/// the same code path for every backend
+fn print_providers<S: ConfigStore>(cfg: &S) {
+    for name in cfg.provider_names() {
+        let pcfg = cfg.provider_config(&name).unwrap();
+        println!("provider {} kind={} api_base={:?}", name, pcfg.kind, pcfg.api_base);
+    }
+}
 
 fn main() {
     // config_trait_object impl
@@ -9,22 +17,32 @@ fn main() {
     let cfg_default = config_trait_object::Config::default();
     println!("default:\n{}", cfg_default.to_string().unwrap());
 
-    let mut cfg = config_trait_object::Config::from_str().unwrap();
-    println!("cfg_str:\n{}", cfg.to_string().unwrap());
+    let shared = SharedConfig::new(config_trait_object::Config::from_str().unwrap());
+    println!("cfg_str:\n{}", shared.snapshot().to_string().unwrap());
 
-    println!("cfg.provider: {}", cfg.provider());
+    println!("cfg.provider: {}", shared.snapshot().provider());
 
-    println!("current provider token: {:?}", cfg.current_provider_config().unwrap().token());
+    println!("current provider token: {:?}", shared.current_provider_config().unwrap().token());
+    println!("current provider kind: {}", shared.current_provider_config().unwrap().kind());
 
-    let pcfg = cfg.current_provider_config().unwrap();
+    // writers change a copy, readers keep the snapshot they loaded
+    shared.update(|cfg| {
+        let mut pcfg = dyn_clone::clone_box(cfg.current_provider_config().unwrap());
+        pcfg.set_token("new_token".to_string());
+        cfg.provider_config.set(&cfg.provider(), pcfg);
+        Ok(())
+    }).unwrap();
+    let cfg = shared.snapshot();
+    println!("cfg_str updated:\n{}", cfg.to_string().unwrap());
+    println!("current provider token updated: {:?}", shared.current_provider_config().unwrap().token());
 
-    // update not work because to_owned object free after used
-    let mut pcfg = pcfg.to_owned();
-    pcfg.set_token("new_token".to_string());
+    let personal = cfg.provider_config.get("openai-personal").unwrap();
+    println!("openai-personal extends {:?}: api_base={} timeout={:?}",
+             cfg.provider_config.extends("openai-personal"), personal.api_base(), personal.timeout());
 
-    cfg.provider_config.set(&cfg.provider(), pcfg);
-    println!("cfg_str updated:\n{}", cfg.to_string().unwrap());
-    println!("current provider token updated: {:?}", cfg.current_provider_config().unwrap().token());
+    for (name, pcfg) in cfg.provider_chain() {
+        println!("provider chain: {} api_base={}", name, pcfg.api_base());
+    }
 
     // enum impl
     println!("enum impl --------------------------------------------------");
@@ -32,12 +50,15 @@ fn main() {
     let cfg_default = config_enum::Config::default();
     println!("default:\n{}", cfg_default.to_string().unwrap());
 
-    let mut cfg = config_enum::Config::from_str().unwrap();
+    let cfg = config_enum::Config::from_str().unwrap();
     println!("cfg_str:\n{}", cfg.to_string().unwrap());
 
     println!("cfg.provider: {}", cfg.provider());
 
     println!("current provider config: {:?}", cfg.current_provider_config().unwrap());
+    println!("current provider kind: {}", cfg.current_provider_config().unwrap().kind());
+
+    println!("current provider token: {:?}", cfg.current_provider_config().unwrap().token());
 
     // config_struct_boxed_trait impl
     println!("config_struct_boxed_trait impl --------------------------------------------------");
@@ -45,7 +66,7 @@ fn main() {
     let cfg_default = config_struct_boxed_trait::Config::default();
     println!("default:\n{}", cfg_default.to_string().unwrap());
 
-    let mut cfg = config_struct_boxed_trait::Config::from_str().unwrap();
+    let cfg = config_struct_boxed_trait::Config::from_str().unwrap();
     println!("cfg_str:\n{}", cfg.to_string().unwrap());
 
     println!("cfg.provider: {}", cfg.provider());
@@ -62,11 +83,18 @@ fn main() {
     println!("cfg.provider: {}", cfg.provider());
 
     println!("current provider token: {:?}", cfg.current_provider_config().unwrap().token);
+    println!("current provider kind: {:?}", cfg.provider_kind(cfg.provider()));
 
-    let mut pcfg = cfg.current_provider_config().unwrap();
+    let mut pcfg = cfg.current_provider_config().unwrap().clone();
     pcfg.token = Some("new_token".to_string());
 
-    cfg.provider_config.insert(cfg.provider(), pcfg);
+    cfg.provider_config.insert(cfg.provider().to_string(), pcfg);
     println!("cfg_str updated:\n{}", cfg.to_string().unwrap());
     println!("current provider token updated: {:?}", cfg.current_provider_config().unwrap().token);
+
+    println!("config store --------------------------------------------------");
+    print_providers(&config_trait_object::Config::from_str().unwrap());
+    print_providers(&config_enum::Config::from_str().unwrap());
+    print_providers(&config_struct_boxed_trait::Config::from_str().unwrap());
+    print_providers(&config_struct::Config::from_str().unwrap());
 }
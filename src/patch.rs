@@ -0,0 +1,96 @@
+//! partial updates of a `config_trait_object::Config` as RFC 7386 merge patches or RFC 6902 patches
+//!
+//! a patch applies to the document `to_string()` writes, so `/provider_config/<name>/<field>` addresses
+//! a provider config field, and an entry that `extends` another holds only its own fields.
+//! the patched config is loaded again like a file, then the active provider and every provider config
+//! the patch touched are validated. a provider config changing its kind is rejected unless allowed.
+//! the values a patch brings are literal, `${...}` in them is escaped and never interpolated.
+
+use std::collections::BTreeSet;
+use log::debug;
+use serde_json::Value;
+use crate::audit::audit_changes;
+use crate::config_trait_object::Config;
+use crate::interpolate::escape;
+use crate::transaction::{join, ValidationError};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PatchOptions {
+    /// let a patch change the `provider` tag of an existing provider config
+    pub allow_kind_change: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("invalid patch: {0}")]
+    Patch(String),
+    /// the patched document is not a config
+    #[error("patched config does not load: {0}")]
+    Config(anyhow::Error),
+    #[error("provider config {provider} would change kind from {from} to {to}")]
+    KindChange { provider: String, from: String, to: String },
+    #[error("patched config is invalid: {}", join(.0))]
+    Invalid(Vec<ValidationError>),
+}
+
+impl Config {
+    /// apply an RFC 7386 merge patch, e.g. `{"provider_config": {"OpenaiMobile": {"model": "gpt-4"}}}`
+    pub fn apply_merge_patch(&mut self, patch: &Value, options: PatchOptions) -> Result<(), PatchError> {
+        let mut patch = patch.clone();
+        escape(&mut patch);
+        let mut doc = self.to_document().map_err(PatchError::Config)?;
+        json_patch::merge(&mut doc, &patch);
+        self.replace_with(doc, options)
+    }
+
+    /// apply an RFC 6902 patch, e.g. `[{"op": "replace", "path": "/provider_config/OpenaiMobile/model", "value": "gpt-4"}]`
+    pub fn apply_json_patch(&mut self, patch: &Value, options: PatchOptions) -> Result<(), PatchError> {
+        let mut patch: json_patch::Patch = serde_json::from_value(patch.clone())
+            .map_err(|e| PatchError::Patch(e.to_string()))?;
+        for operation in &mut patch.0 {
+            match operation {
+                json_patch::PatchOperation::Add(json_patch::AddOperation { value, .. })
+                | json_patch::PatchOperation::Replace(json_patch::ReplaceOperation { value, .. })
+                | json_patch::PatchOperation::Test(json_patch::TestOperation { value, .. }) => escape(value),
+                _ => {}
+            }
+        }
+        let mut doc = self.to_document().map_err(PatchError::Config)?;
+        json_patch::patch(&mut doc, &patch).map_err(|e| PatchError::Patch(e.to_string()))?;
+        self.replace_with(doc, options)
+    }
+
+    /// load the patched document and check it before it replaces this config
+    fn replace_with(&mut self, doc: Value, options: PatchOptions) -> Result<(), PatchError> {
+        let before = self.to_document().map_err(PatchError::Config)?;
+        let mut patched = Config::try_from(doc).map_err(PatchError::Config)?;
+
+        let entries = |doc: &Value| doc.get("provider_config").and_then(|entries| entries.as_object()).cloned().unwrap_or_default();
+        let (old_entries, new_entries) = (entries(&before), entries(&patched.to_document().map_err(PatchError::Config)?));
+        let mut changed: BTreeSet<String> = old_entries.keys().chain(new_entries.keys())
+            .filter(|name| old_entries.get(*name) != new_entries.get(*name))
+            .cloned()
+            .collect();
+
+        if !options.allow_kind_change {
+            for name in &changed {
+                if let (Some(from), Some(to)) = (self.provider_config.kind(name), patched.provider_config.kind(name)) {
+                    if from != to {
+                        return Err(PatchError::KindChange { provider: name.clone(), from: from.to_string(), to: to.to_string() });
+                    }
+                }
+            }
+        }
+
+        changed.insert(patched.provider());
+        let errors = patched.validate_providers(&changed);
+        if !errors.is_empty() {
+            return Err(PatchError::Invalid(errors));
+        }
+        debug!("patch applied to {:?}", changed);
+        audit_changes(self, &patched, changed.iter().map(|name| name.as_str()));
+        patched.source = self.source.take();
+        *self = patched;
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! loading a `config_trait_object::Config` from a file and saving it back safely
+//!
+//! `save` writes a temp file next to the target, fsyncs it and renames it over the target,
+//! so readers see the old or the new file, never a partial one. the file is created `0600`
+//! because it holds tokens. writers take an advisory lock on `<path>.lock`, and a file that
+//! changed since it was loaded is not overwritten.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use log::debug;
+use crate::audit::AuditEvent;
+use crate::config_trait_object::Config;
+use crate::store::ConfigStore;
+
+/// the file a config was loaded from or saved to, and a hash of its contents at that time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    path: PathBuf,
+    hash: u64,
+}
+
+impl Source {
+    /// the source of a config read from `contents` of the file at `path`
+    pub(crate) fn from_contents(path: &Path, contents: &[u8]) -> Self {
+        Source { path: path.to_path_buf(), hash: content_hash(contents) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn content_hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// an advisory lock on `<path>.lock`, released on drop
+pub(crate) fn lock(path: &Path) -> anyhow::Result<File> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = OpenOptions::new().create(true).truncate(false).write(true).mode(0o600).open(&lock_path)?;
+    file.lock()?;
+    Ok(file)
+}
+
+impl Config {
+    /// load a YAML config, remembering the file so [`save`](Config::save) can detect changes to it
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)
+            .map_err(|e| anyhow::format_err!("read {}: {}", path.display(), e))?;
+        let mut config = Config::from_yaml(std::str::from_utf8(&contents)?)?;
+        config.source = Some(Source::from_contents(path, &contents));
+        AuditEvent::load(path).emit();
+        Ok(config)
+    }
+
+    /// write the config to `path` atomically, see the module docs,
+    /// an existing file must be the one this config was loaded from, unchanged since
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let contents = ConfigStore::to_string(self)?;
+        let _lock = lock(path)?;
+
+        match std::fs::read(path) {
+            Ok(current) => match &self.source {
+                Some(source) if source.path == path && source.hash == content_hash(&current) => {}
+                Some(source) if source.path == path => {
+                    anyhow::bail!("{} changed since it was loaded, not overwriting it", path.display());
+                }
+                _ => anyhow::bail!("{} exists and was not loaded into this config, not overwriting it", path.display()),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => anyhow::bail!("read {}: {}", path.display(), e),
+        }
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let file_name = path.file_name()
+            .ok_or_else(|| anyhow::format_err!("{} is not a file path", path.display()))?;
+        let tmp = dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+        let written = (|| -> anyhow::Result<()> {
+            let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp)?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?;
+            std::fs::rename(&tmp, path)?;
+            File::open(dir)?.sync_all()?;
+            Ok(())
+        })();
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&tmp);
+            anyhow::bail!("save {}: {}", path.display(), e);
+        }
+
+        debug!("saved config to {}", path.display());
+        AuditEvent::save(path).emit();
+        self.source = Some(Source::from_contents(path, contents.as_bytes()));
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use chrono::{DateTime, TimeDelta, Utc};
+#[cfg(any(feature = "trait-object", feature = "enum"))]
+pub use provider_config_derive::ProviderAccess;
+
+/// read and update the settings of a provider config,
+/// implemented by the `Box<dyn ProviderConfig>` of `config_trait_object`
+/// and by the `ProviderConfig` enum of `config_enum`
+pub trait ProviderAccess {
+    fn api_base(&self) -> String;
+    fn token(&self) -> String;
+    fn set_token(&mut self, token: String);
+    fn cookies(&self) -> Option<HashMap<String, String>>;
+    fn set_cookies(&mut self, cookies: HashMap<String, String>);
+    fn model(&self) -> Option<String>;
+    fn timeout(&self) -> Option<Duration>;
+    /// when the token or session cookies expire, `None` when unknown
+    fn expires_at(&self) -> Option<DateTime<Utc>>;
+    fn set_expires_at(&mut self, expires_at: Option<DateTime<Utc>>);
+    /// check the config is usable for sending requests, e.g. the token is not empty
+    fn validate(&self) -> anyhow::Result<()>;
+
+    fn is_expired(&self) -> bool {
+        self.expires_within(Duration::ZERO)
+    }
+
+    /// the credentials expire within `duration` from now, or already have
+    fn expires_within(&self, duration: Duration) -> bool {
+        let duration = TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+        self.expires_at().is_some_and(|expires_at| expires_at - Utc::now() <= duration)
+    }
+}
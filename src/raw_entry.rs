@@ -0,0 +1,266 @@
+//! a config document read without building a `serde_json::Value` for its provider configs:
+//! each `provider_config` entry is copied from the deserializer straight into JSON text,
+//! noting on the way what loading needs before the entry is deserialized
+
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeSeed, Error, MapAccess, SeqAccess, Visitor};
+use serde_json::value::RawValue;
+
+/// a config document, `provider_config` as written and every other top-level value as it is
+pub(crate) struct Document {
+    pub header: serde_json::Map<String, serde_json::Value>,
+    pub provider_config: Option<Vec<(String, RawEntry)>>,
+}
+
+/// a provider config as written
+pub(crate) struct RawEntry {
+    pub json: Box<RawValue>,
+    /// the `provider` tag, when the entry sets one
+    pub provider: Option<String>,
+    /// the entry named by `extends`
+    pub extends: Option<String>,
+    /// a string value holds `${`, so interpolation may change the entry
+    pub templated: bool,
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DocumentVisitor)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a config map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Document, A::Error> {
+        let mut document = Document { header: serde_json::Map::new(), provider_config: None };
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "provider_config" {
+                document.provider_config = Some(map.next_value::<RawEntries>()?.0);
+            } else {
+                document.header.insert(key, map.next_value()?);
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// `provider_config`, entries in document order
+pub(crate) struct RawEntries(pub Vec<(String, RawEntry)>);
+
+impl<'de> Deserialize<'de> for RawEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+struct EntriesVisitor;
+
+impl<'de> Visitor<'de> for EntriesVisitor {
+    type Value = RawEntries;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of provider configs")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawEntries, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(name) = map.next_key::<String>()? {
+            let entry = map.next_value_seed(EntrySeed)
+                .map_err(|e| A::Error::custom(format!("provider config {}: {}", name, e)))?;
+            entries.push((name, entry));
+        }
+        Ok(RawEntries(entries))
+    }
+}
+
+struct EntrySeed;
+
+impl<'de> DeserializeSeed<'de> for EntrySeed {
+    type Value = RawEntry;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<RawEntry, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntrySeed {
+    type Value = RawEntry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a provider config map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawEntry, A::Error> {
+        let mut out = Vec::with_capacity(128);
+        let (mut provider, mut extends, mut templated) = (None, None, false);
+        out.push(b'{');
+        let mut first = true;
+        while let Some(field) = map.next_key_seed(Key { out: &mut out, comma: !first })? {
+            first = false;
+            let mut captured = None;
+            let capture = matches!(field, Field::Provider | Field::Extends).then_some(&mut captured);
+            map.next_value_seed(Transcode { out: &mut out, templated: &mut templated, capture, comma: false })?;
+            match field {
+                Field::Provider => provider = captured,
+                Field::Extends if captured.is_none() => return Err(A::Error::custom("extends must be an entry name")),
+                Field::Extends => extends = captured,
+                Field::Other => {}
+            }
+        }
+        out.push(b'}');
+        let json = String::from_utf8(out).map_err(A::Error::custom)?;
+        Ok(RawEntry { json: RawValue::from_string(json).map_err(A::Error::custom)?, provider, extends, templated })
+    }
+}
+
+enum Field {
+    Provider,
+    Extends,
+    Other,
+}
+
+/// writes a map key, preceded by a `,` unless it is the first, and the `:` after it
+struct Key<'a> {
+    out: &'a mut Vec<u8>,
+    comma: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for Key<'_> {
+    type Value = Field;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Field, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Key<'_> {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Field, E> {
+        if self.comma {
+            self.out.push(b',');
+        }
+        write(self.out, v)?;
+        self.out.push(b':');
+        Ok(match v {
+            "provider" => Field::Provider,
+            "extends" => Field::Extends,
+            _ => Field::Other,
+        })
+    }
+}
+
+/// writes any value as JSON, preceded by a `,` unless it is the first item of a sequence,
+/// a top-level string is also kept in `capture`
+struct Transcode<'a> {
+    out: &'a mut Vec<u8>,
+    templated: &'a mut bool,
+    capture: Option<&'a mut Option<String>>,
+    comma: bool,
+}
+
+impl Transcode<'_> {
+    /// the same for a nested value
+    fn nested(&mut self, comma: bool) -> Transcode<'_> {
+        Transcode { out: &mut *self.out, templated: &mut *self.templated, capture: None, comma }
+    }
+}
+
+fn write<T: Serialize + ?Sized, E: Error>(out: &mut Vec<u8>, value: &T) -> Result<(), E> {
+    serde_json::to_writer(out, value).map_err(E::custom)
+}
+
+impl<'de> DeserializeSeed<'de> for Transcode<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.comma {
+            self.out.push(b',');
+        }
+        deserializer.deserialize_any(Transcode { comma: false, ..self })
+    }
+}
+
+impl<'de> Visitor<'de> for Transcode<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<(), E> {
+        write(self.out, &v)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<(), E> {
+        write(self.out, &v)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<(), E> {
+        write(self.out, &v)
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<(), E> {
+        write(self.out, &v)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<(), E> {
+        if v.contains("${") {
+            *self.templated = true;
+        }
+        if let Some(capture) = self.capture {
+            *capture = Some(v.to_string());
+        }
+        write(self.out, v)
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<(), E> {
+        self.out.extend_from_slice(b"null");
+        Ok(())
+    }
+
+    fn visit_none<E: Error>(self) -> Result<(), E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        self.out.push(b'[');
+        let mut first = true;
+        while seq.next_element_seed(self.nested(!first))?.is_some() {
+            first = false;
+        }
+        self.out.push(b']');
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        self.out.push(b'{');
+        let mut first = true;
+        while map.next_key_seed(Key { out: &mut *self.out, comma: !first })?.is_some() {
+            first = false;
+            map.next_value_seed(self.nested(false))?;
+        }
+        self.out.push(b'}');
+        Ok(())
+    }
+}
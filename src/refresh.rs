@@ -0,0 +1,143 @@
+//! refresh expiring credentials of a [`SharedConfig`] in the background
+//!
+//! a [`Refresher`] looks for provider configs whose `expires_at` falls within its margin,
+//! asks the [`RefreshHook`] for a new [`Credential`] and writes it back in a transaction,
+//! through `set_token`, `set_cookies` and `set_expires_at`. the expiry is only extended when
+//! the kind stores the whole credential, e.g. `Claude2` takes cookies but no token.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use log::{debug, warn};
+use tokio::task::JoinHandle;
+use crate::config_trait_object::ProviderConfig;
+use crate::shared::SharedConfig;
+use crate::transaction::Transaction;
+
+/// a new token, new session cookies, or both
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Credential {
+    pub token: Option<String>,
+    pub cookies: Option<HashMap<String, String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// fetches a new credential for the named provider config,
+/// implemented by any `async` closure taking the name and a copy of the config
+pub trait RefreshHook: Send + Sync + 'static {
+    fn refresh(&self, provider: String, config: Box<dyn ProviderConfig>) -> BoxFuture<'static, anyhow::Result<Credential>>;
+}
+
+impl<F, Fut> RefreshHook for F
+where
+    F: Fn(String, Box<dyn ProviderConfig>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<Credential>> + Send + 'static,
+{
+    fn refresh(&self, provider: String, config: Box<dyn ProviderConfig>) -> BoxFuture<'static, anyhow::Result<Credential>> {
+        Box::pin(self(provider, config))
+    }
+}
+
+pub struct Refresher {
+    config: SharedConfig,
+    hook: Arc<dyn RefreshHook>,
+    margin: Duration,
+    interval: Duration,
+}
+
+impl Refresher {
+    /// refreshes 5 minutes before expiry, checking every minute
+    pub fn new(config: SharedConfig, hook: impl RefreshHook) -> Self {
+        Refresher {
+            config,
+            hook: Arc::new(hook),
+            margin: Duration::from_secs(5 * 60),
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    /// how long before expiry a credential is refreshed
+    pub fn with_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// how often the background task checks, besides on every config change
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// refresh every credential expiring within the margin, returns the refreshed provider names,
+    /// a failed refresh is logged and tried again on the next call
+    pub async fn refresh_due(&self) -> Vec<String> {
+        let snapshot = self.config.snapshot();
+        let mut due: Vec<(String, Box<dyn ProviderConfig>)> = snapshot.provider_config.names()
+            .filter_map(|name| {
+                let pcfg = snapshot.provider_config.get(name)?;
+                pcfg.expires_within(self.margin).then(|| (name.to_string(), dyn_clone::clone_box(pcfg)))
+            })
+            .collect();
+        due.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut refreshed = Vec::new();
+        for (name, pcfg) in due {
+            debug!("refresh provider config {}, expires at {:?}", name, pcfg.expires_at());
+            let written = match self.hook.refresh(name.clone(), pcfg).await {
+                Ok(credential) => self.config.transaction(|tx| write_back(tx, &name, credential))
+                    .map_err(anyhow::Error::from),
+                Err(e) => Err(e),
+            };
+            match written {
+                Ok(()) => refreshed.push(name),
+                Err(e) => warn!("refresh provider config {}: {}", name, e),
+            }
+        }
+        refreshed
+    }
+
+    /// run [`refresh_due`](Self::refresh_due) now, then every interval and after every config change
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut changes = self.config.subscribe();
+            loop {
+                self.refresh_due().await;
+                // our own writes are no reason to check again
+                changes.borrow_and_update();
+                tokio::select! {
+                    _ = tokio::time::sleep(self.interval) => {}
+                    changed = changes.changed() => if changed.is_err() {
+                        return;
+                    },
+                }
+            }
+        })
+    }
+}
+
+/// set the credential and its expiry, an error when the kind has nowhere to keep part of it,
+/// the old credential must not look valid for longer
+fn write_back(tx: &mut Transaction<'_>, name: &str, credential: Credential) -> anyhow::Result<()> {
+    if credential.token.is_none() && credential.cookies.is_none() {
+        anyhow::bail!("refreshed credential has neither a token nor cookies");
+    }
+    if let Some(token) = &credential.token {
+        tx.set_token(name, token)?;
+    }
+    if let Some(cookies) = &credential.cookies {
+        tx.set_cookies(name, cookies.clone())?;
+    }
+    // kinds without a token or cookies field ignore the setter, which only shows on reading it back
+    let pcfg = tx.config().provider_config.try_get(name)?
+        .ok_or_else(|| anyhow::format_err!("no provider config {}", name))?;
+    if credential.token.as_ref().is_some_and(|token| pcfg.token() != *token) {
+        anyhow::bail!("a {} provider config has no token to refresh", pcfg.kind());
+    }
+    if credential.cookies.is_some() && pcfg.cookies() != credential.cookies {
+        anyhow::bail!("a {} provider config has no cookies to refresh", pcfg.kind());
+    }
+    tx.set_expires_at(name, credential.expires_at)
+}
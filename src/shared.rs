@@ -0,0 +1,111 @@
+//! a `config_trait_object::Config` shared between tasks and threads
+//!
+//! readers load an immutable `Arc<Config>` snapshot without locking, writers are serialized,
+//! each copies the current config, changes the copy and publishes it as the new snapshot
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use arc_swap::ArcSwap;
+use log::debug;
+use tokio::sync::watch;
+use crate::audit::audit_changes;
+use crate::config_trait_object::{Config, ProviderConfig};
+use crate::transaction::{Transaction, TransactionError};
+
+/// cheap to clone, all clones share the same config
+#[derive(Clone)]
+pub struct SharedConfig {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    current: ArcSwap<Config>,
+    /// held while a writer builds the next snapshot
+    writer: Mutex<()>,
+    changes: watch::Sender<Arc<Config>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        let config = Arc::new(config);
+        SharedConfig {
+            inner: Arc::new(Inner {
+                current: ArcSwap::new(config.clone()),
+                writer: Mutex::new(()),
+                changes: watch::channel(config).0,
+            }),
+        }
+    }
+
+    /// the current config, unaffected by later updates
+    pub fn snapshot(&self) -> Arc<Config> {
+        self.inner.current.load_full()
+    }
+
+    /// the current provider config, read from a snapshot
+    pub fn current_provider_config(&self) -> Option<ProviderSnapshot> {
+        let config = self.snapshot();
+        let provider = config.provider();
+        config.provider_config.get(&provider)?;
+        Some(ProviderSnapshot { config, provider })
+    }
+
+    /// change the config, the change is published only when `f` succeeds
+    pub fn update<R>(&self, f: impl FnOnce(&mut Config) -> anyhow::Result<R>) -> anyhow::Result<R> {
+        self.publish(f, true)
+    }
+
+    fn publish<R>(&self, f: impl FnOnce(&mut Config) -> anyhow::Result<R>, audit: bool) -> anyhow::Result<R> {
+        let _writer = self.inner.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let old = self.inner.current.load_full();
+        let mut config = Config::clone(&old);
+        let result = f(&mut config)?;
+        if audit {
+            audit_changes(&old, &config, config.provider_config.loaded_names());
+        }
+        let config = Arc::new(config);
+        self.inner.current.store(config.clone());
+        self.inner.changes.send_replace(config);
+        debug!("shared config updated");
+        Ok(result)
+    }
+
+    /// run a [`transaction`](Config::transaction) on the current config and publish it on commit,
+    /// subscribers see one new snapshot per commit and none for a rollback
+    pub fn transaction<R>(&self, f: impl FnOnce(&mut Transaction<'_>) -> anyhow::Result<R>) -> Result<R, TransactionError> {
+        // the transaction audits its own commit
+        self.publish(|config| Ok(config.transaction(f)?), false)
+            .map_err(|e| e.downcast::<TransactionError>().unwrap_or_else(TransactionError::Aborted))
+    }
+
+    /// the receiver holds the current snapshot, `changed()` wakes up on every update
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.inner.changes.subscribe()
+    }
+}
+
+/// a provider config together with the snapshot it lives in
+pub struct ProviderSnapshot {
+    config: Arc<Config>,
+    provider: String,
+}
+
+impl ProviderSnapshot {
+    /// the instance name
+    pub fn name(&self) -> &str {
+        &self.provider
+    }
+
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+}
+
+impl Deref for ProviderSnapshot {
+    type Target = dyn ProviderConfig;
+
+    fn deref(&self) -> &Self::Target {
+        // checked when the snapshot was taken, and the parsed entry is cached since
+        self.config.provider_config.get(&self.provider).expect("provider config in snapshot")
+    }
+}
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// a provider kind every backend knows
+struct Kind {
+    /// the name `ProviderView` uses, the `provider` tag of `config_trait_object`
+    name: &'static str,
+    /// the name `config_struct` and `config_struct_boxed_trait` use
+    snake_case: &'static str,
+    /// what `api_base` and `model` read as when the config sets none, as `ProviderAccess` has them
+    api_base: &'static str,
+    model: Option<&'static str>,
+}
+
+const KINDS: &[Kind] = &[
+    Kind { name: "OpenaiMobile", snake_case: "openai_mobile", api_base: "https://api.openai.com", model: Some("davinci") },
+    Kind { name: "Claude2", snake_case: "claude2", api_base: "https://claude.io", model: Some("claude2") },
+];
+
+/// the kind named `kind` in either spelling
+fn known_kind(kind: &str) -> Option<&'static Kind> {
+    KINDS.iter().find(|known| known.name == kind || known.snake_case == kind)
+}
+
+/// the settings of one provider instance, the same for every backend: the kind is spelled
+/// like the `provider` tag, and an unset `api_base` or `model` reads as the kind's default
+///
+/// fields borrow from the config where the backend stores them as is,
+/// so reading the current provider does not allocate for `config_struct` and `config_struct_boxed_trait`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProviderView<'a> {
+    /// the instance name, the key in `provider_config`
+    pub name: Cow<'a, str>,
+    /// the provider kind, `OpenaiMobile` or `Claude2` whatever the backend's own spelling
+    pub kind: Cow<'a, str>,
+    pub api_base: Option<Cow<'a, str>>,
+    pub token: Option<Cow<'a, str>>,
+    pub model: Option<Cow<'a, str>>,
+    pub cookies: Cow<'a, HashMap<String, String>>,
+    /// request timeout in seconds
+    pub timeout: Option<u64>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ProviderView<'_> {
+    /// spell a known kind like the `provider` tag and fill in the `api_base` and `model` the config leaves unset
+    pub fn normalized(mut self) -> Self {
+        if let Some(kind) = known_kind(&self.kind) {
+            self.kind = Cow::Borrowed(kind.name);
+            self.api_base.get_or_insert(Cow::Borrowed(kind.api_base));
+            if self.model.is_none() {
+                self.model = kind.model.map(Cow::Borrowed);
+            }
+        }
+        self
+    }
+
+    /// detach the view from the config it was read from
+    pub fn into_owned(self) -> ProviderView<'static> {
+        ProviderView {
+            name: Cow::Owned(self.name.into_owned()),
+            kind: Cow::Owned(self.kind.into_owned()),
+            api_base: self.api_base.map(|s| Cow::Owned(s.into_owned())),
+            token: self.token.map(|s| Cow::Owned(s.into_owned())),
+            model: self.model.map(|s| Cow::Owned(s.into_owned())),
+            cookies: Cow::Owned(self.cookies.into_owned()),
+            timeout: self.timeout,
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// the config API shared by all backends, so consumers can be generic over the representation
+pub trait ConfigStore {
+    /// the active provider instance
+    fn provider(&self) -> Cow<'_, str>;
+
+    /// the provider instances in the order they are tried, the active one first
+    fn providers(&self) -> Vec<String> {
+        vec![self.provider().into_owned()]
+    }
+
+    /// settings of the provider instance `provider`
+    fn provider_config(&self, provider: &str) -> Option<ProviderView<'_>>;
+
+    /// all provider instance names, sorted
+    fn provider_names(&self) -> Vec<String>;
+
+    /// make `provider` the active provider instance, it must exist
+    fn set_active(&mut self, provider: &str) -> anyhow::Result<()>;
+
+    fn to_string(&self) -> anyhow::Result<String>;
+
+    fn from_yaml(contents: &str) -> anyhow::Result<Self> where Self: Sized;
+
+    /// get current provider config
+    fn current_provider_config(&self) -> Option<ProviderView<'_>> {
+        self.provider_config(&self.provider())
+    }
+}
@@ -0,0 +1,135 @@
+//! multi-step edits of a `config_trait_object::Config` that apply all together or not at all
+//!
+//! the edits go to a working copy, which is validated before it replaces the config,
+//! so a failed transaction leaves the config as it was
+
+use std::collections::{BTreeSet, HashMap};
+use chrono::{DateTime, Utc};
+use log::debug;
+use crate::audit::audit_changes;
+use crate::config_trait_object::{Config, ProviderConfig, ProviderSelection};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransactionError {
+    /// the transaction closure returned an error
+    #[error("transaction aborted: {0}")]
+    Aborted(anyhow::Error),
+    /// the edited config failed validation
+    #[error("transaction rolled back: {}", join(.0))]
+    Invalid(Vec<ValidationError>),
+}
+
+/// why one provider config made the edited config invalid
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("provider config {provider}: {message}")]
+pub struct ValidationError {
+    pub provider: String,
+    pub message: String,
+}
+
+pub(crate) fn join(errors: &[ValidationError]) -> String {
+    errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// the working copy of a transaction
+pub struct Transaction<'a> {
+    config: &'a mut Config,
+    /// provider configs set, removed or selected, validated on commit
+    changed: BTreeSet<String>,
+}
+
+impl Transaction<'_> {
+    /// the working copy, with the edits made so far
+    pub fn config(&self) -> &Config {
+        self.config
+    }
+
+    /// make `provider` the only selected provider, `fallback` is kept
+    pub fn set_active(&mut self, provider: &str) {
+        self.config.provider = Some(ProviderSelection::Single(provider.to_string()));
+        self.changed.insert(provider.to_string());
+    }
+
+    pub fn set_fallback(&mut self, fallback: Vec<String>) {
+        self.changed.extend(fallback.iter().cloned());
+        self.config.fallback = fallback;
+    }
+
+    pub fn set(&mut self, provider: &str, pcfg: Box<dyn ProviderConfig>) {
+        self.config.provider_config.set(provider, pcfg);
+        self.changed.insert(provider.to_string());
+    }
+
+    pub fn set_token(&mut self, provider: &str, token: &str) -> anyhow::Result<()> {
+        let pcfg = self.config.provider_config.try_get(provider)?
+            .ok_or_else(|| anyhow::format_err!("no provider config {}", provider))?;
+        let mut pcfg = dyn_clone::clone_box(pcfg);
+        pcfg.set_token(token.to_string());
+        self.set(provider, pcfg);
+        Ok(())
+    }
+
+    pub fn set_cookies(&mut self, provider: &str, cookies: HashMap<String, String>) -> anyhow::Result<()> {
+        let pcfg = self.config.provider_config.try_get(provider)?
+            .ok_or_else(|| anyhow::format_err!("no provider config {}", provider))?;
+        let mut pcfg = dyn_clone::clone_box(pcfg);
+        pcfg.set_cookies(cookies);
+        self.set(provider, pcfg);
+        Ok(())
+    }
+
+    pub fn set_expires_at(&mut self, provider: &str, expires_at: Option<DateTime<Utc>>) -> anyhow::Result<()> {
+        let pcfg = self.config.provider_config.try_get(provider)?
+            .ok_or_else(|| anyhow::format_err!("no provider config {}", provider))?;
+        let mut pcfg = dyn_clone::clone_box(pcfg);
+        pcfg.set_expires_at(expires_at);
+        self.set(provider, pcfg);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, provider: &str) -> Option<Box<dyn ProviderConfig>> {
+        self.changed.insert(provider.to_string());
+        self.config.provider_config.remove(provider)
+    }
+}
+
+impl Config {
+    /// run `f` on a working copy of the config and commit the result if it validates,
+    /// the active provider and every provider config touched by `f` must exist and pass `validate()`
+    pub fn transaction<R>(&mut self, f: impl FnOnce(&mut Transaction<'_>) -> anyhow::Result<R>) -> Result<R, TransactionError> {
+        let mut working = self.clone();
+        let mut tx = Transaction { config: &mut working, changed: BTreeSet::new() };
+        let result = f(&mut tx).map_err(TransactionError::Aborted)?;
+        let mut changed = tx.changed;
+
+        changed.insert(working.provider());
+        let errors = working.validate_providers(&changed);
+        if !errors.is_empty() {
+            debug!("transaction rolled back: {}", join(&errors));
+            return Err(TransactionError::Invalid(errors));
+        }
+        debug!("transaction committed: {:?}", changed);
+        audit_changes(self, &working, changed.iter().map(|name| name.as_str()));
+        *self = working;
+        Ok(result)
+    }
+
+    /// check the given provider configs, a missing one is an error only when it is selected
+    pub(crate) fn validate_providers(&self, names: &BTreeSet<String>) -> Vec<ValidationError> {
+        let selected = self.providers();
+        let mut errors = Vec::new();
+        for name in names {
+            let message = match self.provider_config.try_get(name) {
+                Ok(Some(pcfg)) => match pcfg.validate() {
+                    Ok(()) => continue,
+                    Err(e) => e.to_string(),
+                },
+                Ok(None) if selected.contains(name) => "selected but missing".to_string(),
+                Ok(None) => continue,
+                Err(e) => e.to_string(),
+            };
+            errors.push(ValidationError { provider: name.clone(), message });
+        }
+        errors
+    }
+}
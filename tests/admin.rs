@@ -0,0 +1,109 @@
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use serde_json::{json, Value};
+use serde_trait_object_demo::admin::{router, REDACTED};
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::shared::SharedConfig;
+use tower::ServiceExt;
+
+struct Reply {
+    status: StatusCode,
+    etag: Option<String>,
+    body: Value,
+}
+
+async fn call(config: &SharedConfig, method: Method, uri: &str, if_match: Option<&str>, body: Option<Value>) -> Reply {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(if_match) = if_match {
+        request = request.header(header::IF_MATCH, if_match);
+    }
+    let request = match body {
+        Some(body) => request.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    };
+    let response = router(config.clone()).oneshot(request.unwrap()).await.unwrap();
+    let status = response.status();
+    let etag = response.headers().get(header::ETAG).map(|etag| etag.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    Reply { status, etag, body: serde_json::from_slice(&bytes).unwrap_or(Value::Null) }
+}
+
+fn shared() -> SharedConfig {
+    SharedConfig::new(Config::from_str().unwrap())
+}
+
+#[tokio::test]
+async fn list_and_get_are_redacted() {
+    let config = shared();
+    let list = call(&config, Method::GET, "/providers", None, None).await;
+    assert_eq!(list.status, StatusCode::OK);
+    assert_eq!(list.body["active"], "OpenaiMobile");
+    assert_eq!(list.body["providers"]["OpenaiMobile"]["token"], REDACTED);
+    assert_eq!(list.body["providers"]["Claude2"]["cookies"]["cookie1"], REDACTED);
+    assert!(list.etag.is_some());
+
+    let entry = call(&config, Method::GET, "/providers/openai-personal", None, None).await;
+    assert_eq!(entry.body["api_base"], "https://api.openai.com/v1");
+    assert_eq!(entry.body["token"], REDACTED);
+    assert_eq!(entry.etag, list.etag);
+
+    let missing = call(&config, Method::GET, "/providers/missing", None, None).await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn put_keeps_redacted_secrets_and_checks_etag() {
+    let config = shared();
+    let entry = call(&config, Method::GET, "/providers/OpenaiMobile", None, None).await;
+    let etag = entry.etag.unwrap();
+
+    let mut body = entry.body;
+    body["model"] = json!("gpt-4");
+    let put = call(&config, Method::PUT, "/providers/OpenaiMobile", Some(&etag), Some(body.clone())).await;
+    assert_eq!(put.status, StatusCode::OK);
+    assert_ne!(put.etag.as_ref(), Some(&etag));
+    let pcfg = config.current_provider_config().unwrap();
+    assert_eq!(pcfg.model().as_deref(), Some("gpt-4"));
+    assert_eq!(pcfg.token(), "tk-xxxxxxxx-001");
+    assert_eq!(pcfg.cookies().unwrap()["cookie_foo"], "foo_value");
+
+    let stale = call(&config, Method::PUT, "/providers/OpenaiMobile", Some(&etag), Some(body)).await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn put_validates_before_apply() {
+    let config = shared();
+    let empty_token = json!({ "provider": "OpenaiMobile", "token": "", "cookies": {} });
+    let reply = call(&config, Method::PUT, "/providers/openai-new", None, Some(empty_token)).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(reply.body["error"].as_str().unwrap().contains("token is empty"));
+
+    let mismatch = json!({ "provider": "Claude2", "cookies": { "session": "x" } });
+    let reply = call(&config, Method::PUT, "/providers/OpenaiMobile", None, Some(mismatch)).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert!(config.snapshot().provider_config.get("openai-new").is_none());
+    assert_eq!(config.snapshot().provider_config.get("OpenaiMobile").unwrap().kind(), "OpenaiMobile");
+}
+
+#[tokio::test]
+async fn set_active_provider() {
+    let config = shared();
+    let reply = call(&config, Method::POST, "/active", None, Some(json!({ "provider": "Claude2" }))).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(config.snapshot().provider(), "Claude2");
+
+    let reply = call(&config, Method::POST, "/active", None, Some(json!({ "provider": "missing" }))).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn schema_lists_every_kind() {
+    let reply = call(&shared(), Method::GET, "/schema", None, None).await;
+    let openai = &reply.body["kinds"]["OpenaiMobile"];
+    assert_eq!(openai["properties"]["provider"]["const"], "OpenaiMobile");
+    assert_eq!(openai["properties"]["api_base"]["default"], "https://api.openai.com");
+    assert_eq!(openai["required"], json!(["provider", "token"]));
+    assert_eq!(reply.body["kinds"]["Claude2"]["properties"]["timeout"]["type"], "integer");
+}
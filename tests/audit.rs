@@ -0,0 +1,58 @@
+use serde_trait_object_demo::audit::{self, AuditAction, AuditEvent};
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::shared::SharedConfig;
+use serde_trait_object_demo::store::ConfigStore;
+
+// one test, the audit file and actor are process wide
+#[test]
+fn changes_are_appended_to_audit_file_without_tokens() {
+    let dir = std::env::temp_dir().join(format!("config-audit-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("audit.jsonl");
+    audit::set_audit_file(&log).unwrap();
+    audit::set_actor("alice");
+
+    let path = dir.join("config.yaml");
+    let mut cfg = Config::from_str().unwrap();
+    cfg.save(&path).unwrap();
+    let mut cfg = Config::load(&path).unwrap();
+    cfg.transaction(|tx| {
+        tx.set_active("openai-personal");
+        tx.set_token("openai-personal", "tk-secret-new")
+    }).unwrap();
+    cfg.set_active("Claude2").unwrap();
+
+    let shared = SharedConfig::new(cfg);
+    shared.transaction(|tx| tx.set_token("openai-personal", "tk-secret-shared")).unwrap();
+    shared.update(|config| {
+        config.provider = Some("OpenaiMobile".to_string().into());
+        Ok(())
+    }).unwrap();
+    audit::close_audit_file();
+
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(!contents.contains("tk-"), "token leaked into {}", contents);
+    let events: Vec<AuditEvent> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let actions: Vec<(AuditAction, Option<&str>)> = events.iter()
+        .map(|event| (event.action, event.provider.as_deref()))
+        .collect();
+    assert_eq!(actions, [
+        (AuditAction::Save, None),
+        (AuditAction::Load, None),
+        (AuditAction::ProviderSwitch, Some("openai-personal")),
+        (AuditAction::TokenChange, Some("openai-personal")),
+        (AuditAction::ProviderSwitch, Some("Claude2")),
+        (AuditAction::TokenChange, Some("openai-personal")),
+        (AuditAction::ProviderSwitch, Some("OpenaiMobile")),
+    ]);
+    assert!(events.iter().all(|event| event.actor == "alice"));
+    assert_eq!(events[0].path.as_deref(), Some(path.to_str().unwrap()));
+    assert_eq!(events[2].old.as_deref(), Some("OpenaiMobile"));
+
+    let token = &events[3];
+    assert_eq!(token.field.as_deref(), Some("token"));
+    assert_eq!(token.new, Some(audit::redact("tk-secret-new")));
+    assert_ne!(token.old, token.new);
+    assert!(token.new.as_ref().unwrap().starts_with("***"));
+}
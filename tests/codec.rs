@@ -0,0 +1,54 @@
+use futures::{SinkExt, StreamExt};
+use serde_trait_object_demo::codec::{ConfigCodec, ProviderConfigCodec};
+use serde_trait_object_demo::config_trait_object::Config;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+#[tokio::test]
+async fn configs_over_duplex() {
+    let (client, server) = tokio::io::duplex(64);
+    let mut tx = FramedWrite::new(client, ConfigCodec::new());
+    let mut rx = FramedRead::new(server, ConfigCodec::new());
+
+    let mut cfg = Config::from_str().unwrap();
+    let sender = tokio::spawn(async move {
+        tx.send(&cfg).await.unwrap();
+        let mut pcfg = dyn_clone::clone_box(cfg.current_provider_config().unwrap());
+        pcfg.set_token("new_token".to_string());
+        cfg.provider_config.set(&cfg.provider(), pcfg);
+        tx.send(&cfg).await.unwrap();
+    });
+
+    let first = rx.next().await.unwrap().unwrap();
+    assert_eq!(first.current_provider_config().unwrap().token(), "tk-xxxxxxxx-001");
+    assert_eq!(first.provider_config.extends("openai-personal"), Some("OpenaiMobile"));
+    let second = rx.next().await.unwrap().unwrap();
+    assert_eq!(second.current_provider_config().unwrap().token(), "new_token");
+    sender.await.unwrap();
+    assert!(rx.next().await.is_none());
+}
+
+#[tokio::test]
+async fn provider_configs_over_duplex() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut tx = FramedWrite::new(client, ProviderConfigCodec::new());
+    let mut rx = FramedRead::new(server, ProviderConfigCodec::new());
+
+    let cfg = Config::from_str().unwrap();
+    tx.send(cfg.provider_config.get("Claude2").unwrap()).await.unwrap();
+    drop(tx);
+
+    let pcfg = rx.next().await.unwrap().unwrap();
+    assert_eq!(pcfg.kind(), "Claude2");
+    assert_eq!(pcfg.cookies().unwrap()["cookie1"], "cookie1-value");
+    assert!(rx.next().await.is_none());
+}
+
+#[tokio::test]
+async fn oversized_frame_is_an_error() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut tx = FramedWrite::new(client, ConfigCodec::new());
+    let mut rx = FramedRead::new(server, ConfigCodec::with_max_frame_length(16));
+
+    tx.send(&Config::default()).await.unwrap();
+    assert!(rx.next().await.unwrap().is_err());
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use serde_trait_object_demo::config_trait_object::{externally_tagged, Claude2, Config, ProviderConfig};
+
+/// compare as YAML values, the order of the provider configs is not kept
+fn yaml(cfg: &Config) -> serde_yaml::Value {
+    serde_yaml::to_value(cfg).unwrap()
+}
+
+#[test]
+fn bincode_round_trip() {
+    let mut cfg = Config::from_str().unwrap();
+    let mut claude = dyn_clone::clone_box(cfg.provider_config.get("Claude2").unwrap());
+    claude.set_expires_at(Some("2030-01-01T00:00:00Z".parse().unwrap()));
+    cfg.provider_config.set("Claude2", claude);
+    // the serde impls go through `serde_json::Value`, which bincode can't read back
+    assert!(bincode::deserialize::<Config>(&bincode::serialize(&cfg).unwrap()).is_err());
+
+    let reloaded = Config::from_bincode(&cfg.to_bincode().unwrap()).unwrap();
+    assert_eq!(yaml(&reloaded), yaml(&cfg));
+    assert_eq!(reloaded.provider_config.extends("openai-personal"), Some("OpenaiMobile"));
+    assert_eq!(reloaded.provider_config.get("openai-personal").unwrap().token(), "tk-xxxxxxxx-002");
+}
+
+#[test]
+fn cbor_round_trip() {
+    let cfg = Config::from_str().unwrap();
+    let reloaded = Config::from_cbor(&cfg.to_cbor().unwrap()).unwrap();
+
+    assert_eq!(yaml(&reloaded), yaml(&cfg));
+    assert_eq!(reloaded.providers(), cfg.providers());
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    name: String,
+    #[serde(with = "externally_tagged")]
+    pcfg: Box<dyn ProviderConfig>,
+}
+
+#[test]
+fn externally_tagged_with_bincode() {
+    let pcfg = Claude2 { cookies: [("session".to_string(), "x".to_string())].into(), api_base: None, timeout: Some(5), expires_at: None };
+    let cached = Cached { name: "Claude2".to_string(), pcfg: Box::new(pcfg) };
+
+    let reloaded: Cached = bincode::deserialize(&bincode::serialize(&cached).unwrap()).unwrap();
+    assert_eq!(reloaded.name, "Claude2");
+    assert_eq!(reloaded.pcfg.kind(), "Claude2");
+    assert_eq!(reloaded.pcfg.cookies().unwrap()["session"], "x");
+    assert_eq!(reloaded.pcfg.timeout(), Some(std::time::Duration::from_secs(5)));
+    assert_eq!(reloaded.pcfg.api_base(), "https://claude.io");
+}
+
+#[test]
+fn fields_outside_the_well_known_ones_round_trip() {
+    let cfg: Config = serde_yaml::from_str(r#"
+provider: Claude2
+defaults:
+  retries: 3
+provider_config:
+  Claude2:
+    provider: Claude2
+    cookies: {}
+    api_base: null
+    expires_at: "2030-01-01T00:00:00+00:00"
+"#).unwrap();
+
+    let reloaded = Config::from_bincode(&cfg.to_bincode().unwrap()).unwrap();
+    assert_eq!(yaml(&reloaded), yaml(&cfg));
+    let reloaded = Config::from_cbor(&cfg.to_cbor().unwrap()).unwrap();
+    assert_eq!(yaml(&reloaded), yaml(&cfg));
+    assert_eq!(reloaded.provider_config.get("Claude2").unwrap().kind(), "Claude2");
+}
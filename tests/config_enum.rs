@@ -0,0 +1,47 @@
+use serde_trait_object_demo::config_enum::{Config, ProviderConfig};
+use serde_trait_object_demo::provider::ProviderAccess;
+
+#[test]
+fn from_str_round_trip() {
+    let cfg = Config::from_str().unwrap();
+    let reloaded: Config = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+
+    assert_eq!(reloaded.provider(), "OpenaiMobile");
+    match reloaded.current_provider_config().unwrap() {
+        ProviderConfig::OpenaiMobile(pcfg) => assert_eq!(pcfg.token.as_deref(), Some("tk-xxxxxxxx-001")),
+        pcfg => panic!("unexpected provider config {:?}", pcfg),
+    }
+}
+
+#[test]
+fn instances_resolve_by_name() {
+    let cfg: Config = serde_yaml::from_str(r#"
+provider: openai-personal
+provider_config:
+  openai-personal: !OpenaiMobile
+    token: "tk-personal"
+    api_base: null
+    model: null
+    cookies: {}
+"#).unwrap();
+
+    assert_eq!(cfg.current_provider_config().unwrap().kind(), "OpenaiMobile");
+}
+
+#[test]
+fn provider_access_delegates_to_variant() {
+    let mut cfg = Config::from_str().unwrap();
+    let provider = cfg.provider();
+    let pcfg = cfg.provider_config.get_mut(&provider).unwrap();
+
+    assert_eq!(pcfg.api_base(), "https://api.openai.com");
+    assert_eq!(pcfg.model().as_deref(), Some("davinci"));
+    pcfg.set_token("new_token".to_string());
+    assert_eq!(pcfg.token(), "new_token");
+    assert!(pcfg.validate().is_ok());
+
+    let claude2 = cfg.provider_config.get("Claude2").unwrap();
+    assert_eq!(claude2.token(), "");
+    assert_eq!(claude2.model().as_deref(), Some("claude2"));
+    assert!(claude2.validate().is_ok());
+}
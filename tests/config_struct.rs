@@ -0,0 +1,50 @@
+use std::borrow::Cow;
+use serde_trait_object_demo::config_struct::Config;
+
+#[test]
+fn from_str_round_trip() {
+    let cfg = Config::from_str().unwrap();
+    let reloaded: Config = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+
+    assert_eq!(reloaded.provider(), "openai_mobile");
+    assert_eq!(reloaded.current_provider_config().unwrap().token.as_deref(), Some("tk-xxxxxxxx-001"));
+}
+
+#[test]
+fn provider_kind() {
+    let cfg = Config::from_str().unwrap();
+
+    assert_eq!(cfg.provider_kind("openai_mobile"), Some("openai_mobile"));
+    assert_eq!(cfg.provider_kind("openai-personal"), Some("openai_mobile"));
+    assert_eq!(cfg.provider_kind("missing"), None);
+}
+
+#[test]
+fn lookups_borrow() {
+    let cfg = Config::from_str().unwrap();
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert!(std::ptr::eq(pcfg, &cfg.provider_config["openai_mobile"]));
+    assert!(matches!(cfg.provider_config_or_default("openai_mobile"), Cow::Borrowed(_)));
+    assert!(matches!(cfg.provider_config_or_default("missing"), Cow::Owned(_)));
+}
+
+#[test]
+fn unknown_kinds_are_rejected() {
+    let err = serde_yaml::from_str::<Config>(r#"
+provider: work
+provider_config:
+  work:
+    kind: opneai_mobile
+    token: "tk-work"
+    api_base: null
+    model: null
+    cookies: {}
+  claude3:
+    api_base: null
+    token: null
+    model: null
+    cookies: {}
+"#).unwrap_err();
+    assert!(err.to_string().contains("unknown provider kind of claude3 (claude3), work (opneai_mobile)"), "{}", err);
+}
@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+use serde_trait_object_demo::config_struct_boxed_trait::{Config, OpenaiMobile};
+
+#[test]
+fn from_str_round_trip() {
+    let cfg = Config::from_str().unwrap();
+    let reloaded: Config = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+
+    let pcfg = reloaded.current_provider_config().unwrap();
+    let pcfg = pcfg.as_any().downcast_ref::<OpenaiMobile>().unwrap();
+    assert_eq!(pcfg.token.as_deref(), Some("tk-xxxxxxxx-001"));
+}
+
+#[test]
+fn instances_resolve_by_name() {
+    let mut cfg = Config::from_str().unwrap();
+    cfg.provider = Some("openai-personal".to_string());
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.name(), "openai_mobile");
+    let pcfg = pcfg.as_any().downcast_ref::<OpenaiMobile>().unwrap();
+    assert_eq!(pcfg.token.as_deref(), Some("tk-xxxxxxxx-002"));
+}
+
+#[test]
+fn lookups_borrow() {
+    let cfg = Config::from_str().unwrap();
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    let pcfg = pcfg.as_any().downcast_ref::<OpenaiMobile>().unwrap();
+    assert!(std::ptr::eq(pcfg, &cfg.provider_config.openai_mobile["openai_mobile"]));
+    assert!(matches!(cfg.openai_mobile_or_default("openai_mobile"), Cow::Borrowed(_)));
+    assert!(matches!(cfg.openai_mobile_or_default("claude2"), Cow::Owned(_)));
+    assert!(matches!(cfg.claude2_or_default("claude2"), Cow::Borrowed(_)));
+}
+
+#[test]
+fn instance_names_are_unique_across_kinds() {
+    let err = serde_yaml::from_str::<Config>(r#"
+provider: work
+provider_config:
+  openai_mobile:
+    work:
+      token: "tk-work"
+      api_base: null
+      model: null
+      cookies: {}
+  claude2:
+    work:
+      cookies: {}
+      api_base: null
+"#).unwrap_err();
+    assert!(err.to_string().contains(r#"used by more than one kind: ["work"]"#), "{}", err);
+}
+
+#[test]
+fn single_instance_layout_still_loads() {
+    let cfg = serde_yaml::from_str::<Config>(r#"
+provider: openai_mobile
+provider_config:
+  claude2:
+    cookies:
+        cookie1: "cookie1-value"
+    api_base: "https://claude.io"
+  openai_mobile:
+    token: "tk-xxxxxxxx-001"
+    api_base: "https://api.openai.com"
+    model: "davinci"
+    cookies: {}
+"#).unwrap();
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    let pcfg = pcfg.as_any().downcast_ref::<OpenaiMobile>().unwrap();
+    assert_eq!(pcfg.token.as_deref(), Some("tk-xxxxxxxx-001"));
+    assert_eq!(cfg.claude2_or_default("claude2").cookies["cookie1"], "cookie1-value");
+
+    // saved in the named layout
+    let reloaded: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    assert_eq!(reloaded["provider_config"]["openai_mobile"]["openai_mobile"]["token"], "tk-xxxxxxxx-001");
+}
@@ -0,0 +1,345 @@
+use serde_trait_object_demo::config_trait_object::{Config, OpenaiMobile, ProviderTag};
+
+fn load(yaml: &str) -> anyhow::Result<Config> {
+    Ok(serde_yaml::from_str(yaml)?)
+}
+
+#[test]
+fn from_str_round_trip() {
+    let cfg = Config::from_str().unwrap();
+    let reloaded = load(&cfg.to_string().unwrap()).unwrap();
+
+    assert_eq!(reloaded.provider(), "OpenaiMobile");
+    assert_eq!(reloaded.current_provider_config().unwrap().token(), "tk-xxxxxxxx-001");
+    assert_eq!(reloaded.provider_config.get("openai-personal").unwrap().token(), "tk-xxxxxxxx-002");
+}
+
+#[test]
+fn set_token() {
+    let mut cfg = Config::from_str().unwrap();
+    let mut pcfg = dyn_clone::clone_box(cfg.current_provider_config().unwrap());
+    pcfg.set_token("new_token".to_string());
+    cfg.provider_config.set(&cfg.provider(), pcfg);
+
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "new_token");
+}
+
+#[test]
+fn provider_chain_skips_invalid_and_missing() {
+    let cfg = load(r#"
+provider: [OpenaiMobile, missing]
+fallback: [Claude2, openai-personal]
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    cookies: {}
+  Claude2:
+    provider: Claude2
+    cookies:
+      session: "x"
+  openai-personal:
+    provider: OpenaiMobile
+    token: "tk-2"
+    cookies: {}
+"#).unwrap();
+
+    assert_eq!(cfg.provider(), "OpenaiMobile");
+    assert_eq!(cfg.providers(), ["OpenaiMobile", "missing", "Claude2", "openai-personal"]);
+    let chain: Vec<String> = cfg.provider_chain().map(|(name, _)| name).collect();
+    assert_eq!(chain, ["Claude2", "openai-personal"]);
+}
+
+#[test]
+fn provider_chain_order() {
+    let cfg = load(r#"
+provider: [Claude2, OpenaiMobile]
+fallback: [OpenaiMobile, openai-personal, Claude2]
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    token: "tk-1"
+    cookies: {}
+  Claude2:
+    provider: Claude2
+    cookies:
+      session: "x"
+  openai-personal:
+    provider: OpenaiMobile
+    token: "tk-2"
+    cookies: {}
+"#).unwrap();
+    assert_eq!(cfg.provider(), "Claude2");
+    assert_eq!(cfg.current_provider_config().unwrap().kind(), "Claude2");
+    // each provider once, where it first appears
+    assert_eq!(cfg.providers(), ["Claude2", "OpenaiMobile", "openai-personal"]);
+    let chain: Vec<String> = cfg.provider_chain().map(|(name, _)| name).collect();
+    assert_eq!(chain, cfg.providers());
+
+    let mut cfg = cfg;
+    cfg.provider = None;
+    cfg.fallback.remove(0);
+    assert_eq!(cfg.provider(), "openai-personal");
+    assert_eq!(cfg.providers(), ["openai-personal", "Claude2"]);
+    cfg.fallback.clear();
+    assert_eq!(cfg.provider(), "OpenaiMobile");
+    assert_eq!(cfg.providers(), ["OpenaiMobile"]);
+}
+
+#[test]
+fn instances_resolve_by_name() {
+    let cfg = load(r#"
+provider: openai-work
+provider_config:
+  openai-work:
+    provider: OpenaiMobile
+    token: "tk-work"
+    cookies: {}
+  openai-personal:
+    provider: OpenaiMobile
+    token: "tk-personal"
+    cookies: {}
+"#).unwrap();
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.kind(), "OpenaiMobile");
+    assert_eq!(pcfg.token(), "tk-work");
+    assert!(pcfg.as_any().downcast_ref::<OpenaiMobile>().is_some());
+}
+
+#[test]
+fn provider_tag_must_match_kind_key() {
+    let err = load(r#"
+provider: Claude2
+provider_config:
+  Claude2:
+    provider: OpenaiMobile
+    cookies: {}
+"#).unwrap_err();
+    assert!(err.to_string().contains("does not match its provider tag"), "{}", err);
+
+    let err = load(r#"
+provider: Claude2
+provider_tag: required
+provider_config:
+  Claude2:
+    cookies: {}
+"#).unwrap_err();
+    assert!(err.to_string().contains("has no provider tag"), "{}", err);
+}
+
+#[test]
+fn provider_tag_required_is_kept_on_save() {
+    let cfg = load(r#"
+provider: Claude2
+provider_tag: required
+provider_config:
+  Claude2:
+    provider: Claude2
+    cookies: {}
+"#).unwrap();
+    let saved: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    assert_eq!(saved["provider_tag"], "required");
+    assert_eq!(saved["provider_config"]["Claude2"]["provider"], "Claude2");
+}
+
+#[test]
+fn provider_tag_inferred_from_key() {
+    let cfg = load(r#"
+provider: Claude2
+provider_config:
+  Claude2:
+    cookies: {}
+  claude-work:
+    provider: Claude2
+    cookies: {}
+"#).unwrap();
+    assert_eq!(cfg.provider_tag, ProviderTag::Inferred);
+    assert_eq!(cfg.current_provider_config().unwrap().kind(), "Claude2");
+
+    let saved: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    assert!(saved.get("provider_tag").is_none());
+    assert!(saved["provider_config"]["Claude2"].get("provider").is_none());
+    assert_eq!(saved["provider_config"]["claude-work"]["provider"], "Claude2");
+}
+
+#[test]
+fn extends_and_defaults() {
+    let cfg = load(r#"
+provider: openai-work
+defaults:
+  timeout: 30
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    api_base: "https://proxy.example.com"
+    cookies:
+      team: "shared"
+  openai-work:
+    extends: OpenaiMobile
+    token: "tk-work"
+    timeout: 5
+"#).unwrap();
+
+    let base = cfg.provider_config.get("OpenaiMobile").unwrap();
+    assert_eq!(base.timeout(), Some(std::time::Duration::from_secs(30)));
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.kind(), "OpenaiMobile");
+    assert_eq!(pcfg.api_base(), "https://proxy.example.com");
+    assert_eq!(pcfg.cookies().unwrap()["team"], "shared");
+    assert_eq!(pcfg.timeout(), Some(std::time::Duration::from_secs(5)));
+    assert_eq!(cfg.provider_config.extends("openai-work"), Some("OpenaiMobile"));
+
+    let saved: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    let work = &saved["provider_config"]["openai-work"];
+    assert_eq!(work["extends"], "OpenaiMobile");
+    assert!(work.get("api_base").is_none());
+    assert!(saved["provider_config"]["OpenaiMobile"].get("timeout").is_none());
+}
+
+#[test]
+fn extends_cycle() {
+    let err = load(r#"
+provider: a
+provider_config:
+  a:
+    extends: b
+  b:
+    extends: a
+"#).unwrap_err();
+    assert!(err.to_string().contains("extends cycle"), "{}", err);
+}
+
+#[test]
+fn interpolation() {
+    std::env::set_var("CONFIG_TRAIT_OBJECT_TEST_REGION", "eu");
+    let cfg = load(r#"
+provider: OpenaiMobile
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    api_base: "https://${CONFIG_TRAIT_OBJECT_TEST_REGION}.api.example.com"
+    token: "$${not-a-variable}"
+    model: "${provider_config.Claude2.api_base}"
+    cookies: {}
+  Claude2:
+    provider: Claude2
+    api_base: "https://claude.io"
+    cookies: {}
+"#).unwrap();
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.api_base(), "https://eu.api.example.com");
+    assert_eq!(pcfg.token(), "${not-a-variable}");
+    assert_eq!(pcfg.model().unwrap(), "https://claude.io");
+
+    let err = load(r#"
+provider: OpenaiMobile
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    api_base: "${CONFIG_TRAIT_OBJECT_TEST_UNDEFINED}"
+    cookies: {}
+"#).unwrap_err();
+    assert!(err.to_string().contains("undefined variable"), "{}", err);
+}
+
+#[test]
+fn escaped_values_survive_save_and_reload() {
+    std::env::set_var("CONFIG_TRAIT_OBJECT_TEST_HOME", "/home/someone");
+    let cfg = load(r#"
+provider: OpenaiMobile
+defaults:
+  model: "$$$${CONFIG_TRAIT_OBJECT_TEST_HOME}"
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    token: "abc$${CONFIG_TRAIT_OBJECT_TEST_HOME}def"
+    api_base: "https://$$example.com/${CONFIG_TRAIT_OBJECT_TEST_HOME}"
+    cookies: {}
+"#).unwrap();
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.token(), "abc${CONFIG_TRAIT_OBJECT_TEST_HOME}def");
+    assert_eq!(pcfg.model().unwrap(), "$$${CONFIG_TRAIT_OBJECT_TEST_HOME}");
+
+    let saved: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    let entry = &saved["provider_config"]["OpenaiMobile"];
+    assert_eq!(entry["token"], "abc$${CONFIG_TRAIT_OBJECT_TEST_HOME}def");
+    assert_eq!(entry["api_base"], "https://$$example.com/${CONFIG_TRAIT_OBJECT_TEST_HOME}");
+    assert_eq!(saved["defaults"]["model"], "$$$${CONFIG_TRAIT_OBJECT_TEST_HOME}");
+
+    let reloaded = load(&cfg.to_string().unwrap()).unwrap();
+    let pcfg = reloaded.current_provider_config().unwrap();
+    assert_eq!(pcfg.token(), "abc${CONFIG_TRAIT_OBJECT_TEST_HOME}def");
+    assert_eq!(pcfg.api_base(), "https://$$example.com//home/someone");
+    assert_eq!(pcfg.model().unwrap(), "$$${CONFIG_TRAIT_OBJECT_TEST_HOME}");
+}
+
+#[test]
+fn changed_values_replace_their_templates() {
+    std::env::set_var("CONFIG_TRAIT_OBJECT_TEST_SECRET", "tk-from-env");
+    let mut cfg = load(r#"
+provider: OpenaiMobile
+provider_config:
+  OpenaiMobile:
+    token: "${CONFIG_TRAIT_OBJECT_TEST_SECRET}"
+    api_base: "https://${CONFIG_TRAIT_OBJECT_TEST_SECRET}.example.com"
+    cookies: {}
+  Claude2:
+    cookies:
+      session: "${CONFIG_TRAIT_OBJECT_TEST_SECRET}"
+"#).unwrap();
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "tk-from-env");
+    let saved = cfg.to_string().unwrap();
+    assert!(!saved.contains("tk-from-env"), "{}", saved);
+
+    let mut pcfg = dyn_clone::clone_box(cfg.current_provider_config().unwrap());
+    pcfg.set_token("tk-${new}".to_string());
+    cfg.provider_config.set("OpenaiMobile", pcfg);
+    let saved: serde_yaml::Value = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    let entry = &saved["provider_config"]["OpenaiMobile"];
+    assert_eq!(entry["token"], "tk-$${new}");
+    assert_eq!(entry["api_base"], "https://${CONFIG_TRAIT_OBJECT_TEST_SECRET}.example.com");
+    assert_eq!(saved["provider_config"]["Claude2"]["cookies"]["session"], "${CONFIG_TRAIT_OBJECT_TEST_SECRET}");
+}
+
+#[test]
+fn broken_entry_fails_on_access() {
+    let cfg = load(r#"
+provider: OpenaiMobile
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    token: "tk-1"
+    cookies: {}
+  broken:
+    provider: Claude2
+    cookies: "not a map"
+"#).unwrap();
+
+    assert_eq!(cfg.try_current_provider_config().unwrap().token(), "tk-1");
+    assert!(cfg.provider_config.get("broken").is_none());
+    let err = cfg.provider_config.try_get("broken").err().unwrap();
+    assert!(err.to_string().contains("invalid provider config broken"), "{}", err);
+
+    let reloaded = load(&cfg.to_string().unwrap()).unwrap();
+    assert!(reloaded.provider_config.try_get("broken").is_err());
+}
+
+#[test]
+fn erased_serde_round_trip() {
+    use serde_trait_object_demo::config_trait_object::{deserialize_provider, serialize_provider};
+
+    let cfg = Config::from_str().unwrap();
+    let pcfg = cfg.provider_config.get("openai-personal").unwrap();
+
+    let mut json = Vec::new();
+    serialize_provider(pcfg, &mut <dyn erased_serde::Serializer>::erase(&mut serde_json::Serializer::new(&mut json))).unwrap();
+    let mut de = serde_json::Deserializer::from_slice(&json);
+    let reloaded = deserialize_provider("openai-personal", &mut <dyn erased_serde::Deserializer>::erase(&mut de)).unwrap();
+    assert_eq!(reloaded.kind(), "OpenaiMobile");
+    assert_eq!(reloaded.token(), "tk-xxxxxxxx-002");
+
+    let yaml = serde_yaml::Deserializer::from_str("provider: Claude2\ncookies: {}\n");
+    let err = deserialize_provider("OpenaiMobile", &mut <dyn erased_serde::Deserializer>::erase(yaml)).err().unwrap();
+    assert!(err.to_string().contains("does not match"), "{}", err);
+}
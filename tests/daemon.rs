@@ -0,0 +1,89 @@
+use futures::StreamExt;
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::daemon::{bind_tcp, bind_unix, Client, Daemon};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn serve_tcp(daemon: Daemon) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(daemon.serve_tcp(listener));
+    addr
+}
+
+#[tokio::test]
+async fn requests_over_tcp() {
+    let daemon = Daemon::new(Config::from_str().unwrap());
+    let mut client = Client::connect_tcp(serve_tcp(daemon.clone()).await).await.unwrap();
+
+    let (name, pcfg) = client.current_provider().await.unwrap();
+    assert_eq!(name, "OpenaiMobile");
+    assert_eq!(pcfg.token(), "tk-xxxxxxxx-001");
+
+    let personal = client.get_entry("openai-personal").await.unwrap().unwrap();
+    assert_eq!(personal.api_base(), "https://api.openai.com/v1");
+    assert!(client.get_entry("missing").await.unwrap().is_none());
+
+    client.set_token("openai-personal", "new_token").await.unwrap();
+    assert_eq!(client.get_entry("openai-personal").await.unwrap().unwrap().token(), "new_token");
+    assert_eq!(daemon.config().provider_config.get("openai-personal").unwrap().token(), "new_token");
+
+    let err = client.set_token("missing", "x").await.err().unwrap();
+    assert!(err.to_string().contains("no provider config missing"), "{}", err);
+}
+
+#[tokio::test]
+async fn subscribe_sees_changes() {
+    let addr = serve_tcp(Daemon::new(Config::from_str().unwrap())).await;
+    let mut updates = Box::pin(Client::connect_tcp(addr).await.unwrap().subscribe().await.unwrap());
+
+    let first = updates.next().await.unwrap().unwrap();
+    assert_eq!(first.current_provider_config().unwrap().token(), "tk-xxxxxxxx-001");
+
+    let mut client = Client::connect_tcp(addr).await.unwrap();
+    client.set_token("OpenaiMobile", "new_token").await.unwrap();
+    let second = updates.next().await.unwrap().unwrap();
+    assert_eq!(second.current_provider_config().unwrap().token(), "new_token");
+}
+
+#[tokio::test]
+async fn requests_over_unix_socket() {
+    let path = std::env::temp_dir().join(format!("config-daemon:{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    drop(bind_unix(&path).unwrap());
+    // the socket left behind is replaced
+    let listener = bind_unix(&path).unwrap();
+    tokio::spawn(Daemon::new(Config::from_str().unwrap()).serve_unix(listener));
+
+    let mut client = Client::connect_unix(&path).await.unwrap();
+    let claude = client.get_entry("Claude2").await.unwrap().unwrap();
+    assert_eq!(claude.kind(), "Claude2");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn client_over_any_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let daemon = Daemon::new(Config::from_str().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        daemon.handle(stream).await.unwrap();
+    });
+
+    let mut client = Client::new(TcpStream::connect(addr).await.unwrap());
+    assert_eq!(client.current_provider().await.unwrap().0, "OpenaiMobile");
+}
+
+#[tokio::test]
+async fn only_sockets_and_loopback_addresses_are_bound() {
+    let path = std::env::temp_dir().join(format!("config-daemon-{}.yaml", std::process::id()));
+    std::fs::write(&path, "provider: OpenaiMobile\n").unwrap();
+    let err = bind_unix(&path).err().unwrap();
+    assert!(err.to_string().contains("is not a socket"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "provider: OpenaiMobile\n");
+    std::fs::remove_file(&path).unwrap();
+
+    let err = bind_tcp("0.0.0.0:0".parse().unwrap()).await.err().unwrap();
+    assert!(err.to_string().contains("no authentication"), "{}", err);
+    assert!(bind_tcp("127.0.0.1:0".parse().unwrap()).await.is_ok());
+}
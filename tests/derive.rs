@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use serde_trait_object_demo::config_trait_object::{provider_kinds, Config, ProviderAccess, ProviderConfig};
+
+/// a provider kind defined outside the library
+#[derive(Debug, Clone, Serialize, Deserialize, ProviderConfig)]
+#[provider(model = "gemini-pro")]
+pub struct Gemini {
+    #[provider(required)]
+    pub token: Option<String>,
+    #[provider(default = "https://gemini.example.com")]
+    pub api_base: Option<String>,
+}
+
+#[test]
+fn derived_outside_the_crate() {
+    let cfg: Config = serde_yaml::from_str(r#"
+provider: gemini-work
+provider_config:
+  gemini-work:
+    provider: Gemini
+    token: "tk-gemini"
+"#).unwrap();
+
+    let pcfg = cfg.current_provider_config().unwrap();
+    assert_eq!(pcfg.kind(), "Gemini");
+    assert_eq!(pcfg.token(), "tk-gemini");
+    assert_eq!(pcfg.api_base(), "https://gemini.example.com");
+    assert_eq!(pcfg.model().as_deref(), Some("gemini-pro"));
+    assert!(pcfg.as_any().downcast_ref::<Gemini>().is_some());
+    assert_eq!(pcfg.schema()["required"], serde_json::json!(["provider", "token"]));
+    assert!(Gemini::default().validate().is_err());
+}
+
+#[test]
+fn derived_kinds_are_known_kinds() {
+    let names: Vec<&str> = provider_kinds().iter().map(|kind| kind.name()).collect();
+    assert_eq!(names, ["Claude2", "Gemini", "OpenaiMobile"]);
+    assert_eq!(provider_kinds()[1].default_config().api_base(), "https://gemini.example.com");
+
+    // the tag is inferred from the key
+    let cfg: Config = serde_yaml::from_str(r#"
+provider: Gemini
+provider_config:
+  Gemini:
+    token: "tk-gemini"
+"#).unwrap();
+    assert_eq!(cfg.current_provider_config().unwrap().kind(), "Gemini");
+
+    let err = serde_yaml::from_str::<Config>(r#"
+provider: Gemini
+provider_config:
+  Gemini:
+    provider: OpenaiMobile
+    token: "tk-gemini"
+    cookies: {}
+"#).unwrap_err();
+    assert!(err.to_string().contains("provider config key Gemini does not match its provider tag OpenaiMobile"), "{}", err);
+}
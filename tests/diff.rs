@@ -0,0 +1,137 @@
+use serde_trait_object_demo::diff::{diff, Change, MASK};
+use serde_trait_object_demo::store::ConfigStore;
+use serde_trait_object_demo::{config_enum, config_struct, config_trait_object};
+
+#[test]
+fn trait_object_changes() {
+    let old = config_trait_object::Config::from_str().unwrap();
+    let mut new = old.clone();
+    new.transaction(|tx| {
+        let mut openai = config_trait_object::OpenaiMobile {
+            token: Some("tk-new".to_string()),
+            model: Some("gpt-4".to_string()),
+            ..Default::default()
+        };
+        openai.cookies.insert("cookie_foo".to_string(), "foo_value".to_string());
+        tx.set("OpenaiMobile", Box::new(openai));
+        tx.remove("openai-personal");
+        tx.set_active("Claude2");
+        Ok(())
+    }).unwrap();
+
+    let lines: Vec<String> = diff(&old, &new).changes.iter().map(|change| change.to_string()).collect();
+    assert_eq!(lines, [
+        "provider: OpenaiMobile -> Claude2",
+        // Claude2 was the fallback, now it is the whole chain
+        "fallback: Claude2 -> none",
+        "OpenaiMobile.model: davinci -> gpt-4",
+        "OpenaiMobile.token changed",
+        // the replacement does not carry the defaults' timeout
+        "OpenaiMobile.timeout: 30 -> none",
+        "OpenaiMobile.cookies.cookie_bar removed",
+        "openai-personal removed (OpenaiMobile)",
+    ]);
+    assert!(diff(&old, &old).is_empty());
+}
+
+#[test]
+fn trait_object_timeout_expiry_and_fallback() {
+    let old = config_trait_object::Config::from_yaml(r#"
+provider: a
+fallback: [b]
+provider_config:
+  a:
+    provider: OpenaiMobile
+    token: tk-1
+    cookies: {}
+    timeout: 30
+    expires_at: 2026-01-01T00:00:00Z
+  b:
+    provider: Claude2
+    cookies: {}
+"#).unwrap();
+    let new = config_trait_object::Config::from_yaml(r#"
+provider: a
+provider_config:
+  a:
+    provider: OpenaiMobile
+    token: tk-1
+    cookies: {}
+    timeout: 60
+    expires_at: 2027-01-01T00:00:00Z
+  b:
+    provider: Claude2
+    cookies: {}
+"#).unwrap();
+
+    let lines: Vec<String> = diff(&old, &new).changes.iter().map(|change| change.to_string()).collect();
+    assert_eq!(lines, [
+        "fallback: b -> none",
+        "a.timeout: 30 -> 60",
+        "a.expires_at: 2026-01-01T00:00:00+00:00 -> 2027-01-01T00:00:00+00:00",
+    ]);
+}
+
+#[test]
+fn enum_kind_change_and_masked_cookies() {
+    let old = config_enum::Config::from_yaml(r#"
+provider: a
+provider_config:
+  a: !OpenaiMobile
+    token: tk-1
+    cookies: {}
+  b: !Claude2
+    cookies:
+      cookie1: v1
+"#).unwrap();
+    let new = config_enum::Config::from_yaml(r#"
+provider: a
+provider_config:
+  a: !Claude2
+    cookies:
+      session: s
+  b: !Claude2
+    cookies:
+      cookie1: v2
+"#).unwrap();
+
+    let changes = diff(&old, &new).changes;
+    assert!(changes.contains(&Change::Field {
+        path: "b.cookies.cookie1".to_string(),
+        from: Some(MASK.to_string()),
+        to: Some(MASK.to_string()),
+        secret: true,
+    }));
+    let text = diff(&old, &new).to_string();
+    assert!(text.contains("a: kind OpenaiMobile -> Claude2\n"), "{}", text);
+    assert!(text.contains("a.token removed\n"), "{}", text);
+    assert!(text.contains("a.cookies.session added\n"), "{}", text);
+    assert!(text.contains("b.cookies.cookie1 changed\n"), "{}", text);
+    assert!(!text.contains("v1") && !text.contains("v2") && !text.contains("tk-1"), "{}", text);
+}
+
+#[test]
+fn across_representations_as_json() {
+    let old = config_struct::Config::from_str().unwrap();
+    let mut new = old.clone();
+    new.provider_config.get_mut("claude2").unwrap().model = Some("claude-3".to_string());
+
+    let changes = diff(&old, &new).changes;
+    assert_eq!(changes, [Change::Field {
+        path: "claude2.model".to_string(),
+        from: Some("claude2".to_string()),
+        to: Some("claude-3".to_string()),
+        secret: false,
+    }]);
+
+    let json: serde_json::Value = serde_json::from_str(&diff(&old, &new).to_json().unwrap()).unwrap();
+    assert_eq!(json[0]["change"], "field");
+    assert_eq!(json[0]["path"], "claude2.model");
+
+    // a different backend on each side, e.g. checking a migration
+    let trait_object = config_trait_object::Config::from_str().unwrap();
+    let enum_ = config_enum::Config::from_str().unwrap();
+    let json = serde_json::to_value(diff(&trait_object, &enum_)).unwrap();
+    assert!(json.as_array().unwrap().iter().all(|change| change["from"] != "tk-xxxxxxxx-001"));
+    assert!(diff(&trait_object, &trait_object).to_json().unwrap() == "[]");
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::history::History;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("config-history-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn commit_list_diff_restore() {
+    let dir = temp_dir("restore");
+    let path = dir.join("config.yaml");
+    let history = History::for_config(&path);
+
+    let mut cfg = Config::from_str().unwrap();
+    history.commit(&mut cfg, &path, "alice", None).unwrap();
+
+    cfg.transaction(|tx| {
+        tx.set_active("Claude2");
+        tx.set_token("openai-personal", "new_token")
+    }).unwrap();
+    let entry = history.commit(&mut cfg, &path, "bob", Some("switch to claude")).unwrap();
+    assert_eq!(entry.id, 2);
+    assert_eq!(entry.summary, [
+        "switch to claude",
+        "provider: OpenaiMobile -> Claude2",
+        "fallback: Claude2 -> none",
+        "openai-personal.token changed",
+    ]);
+    assert!(!entry.summary.join(" ").contains("new_token"));
+
+    let entries = history.list().unwrap();
+    assert_eq!(entries.iter().map(|e| e.author.as_str()).collect::<Vec<_>>(), ["alice", "bob"]);
+    assert_eq!(entries[0].summary, ["first snapshot"]);
+    assert_eq!(history.diff(2, 1).unwrap()[0], "provider: Claude2 -> OpenaiMobile");
+
+    let restored = history.restore(1, &path, "carol").unwrap();
+    assert_eq!(restored.id, 3);
+    assert_eq!(restored.summary[0], "restore #1");
+    let cfg = Config::load(&path).unwrap();
+    assert_eq!(cfg.provider(), "OpenaiMobile");
+    assert_eq!(cfg.provider_config.get("openai-personal").unwrap().token(), "tk-xxxxxxxx-002");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_snapshot_is_an_error() {
+    let dir = temp_dir("missing");
+    let history = History::for_config(dir.join("config.yaml"));
+    assert!(history.list().unwrap().is_empty());
+    assert!(history.load(7).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restore_over_a_broken_config() {
+    let dir = temp_dir("broken");
+    let path = dir.join("config.yaml");
+    let history = History::for_config(&path);
+
+    let mut cfg = Config::from_str().unwrap();
+    history.commit(&mut cfg, &path, "alice", None).unwrap();
+    std::fs::write(&path, r#"
+provider: a
+provider_config:
+  a:
+    extends: b
+  b:
+    extends: a
+"#).unwrap();
+    assert!(Config::load(&path).is_err());
+
+    let restored = history.restore(1, &path, "bob").unwrap();
+    assert_eq!(restored.summary[0], "restore #1");
+    assert_eq!(Config::load(&path).unwrap().provider(), "OpenaiMobile");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
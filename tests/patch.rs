@@ -0,0 +1,90 @@
+use serde_json::json;
+use serde_trait_object_demo::config_trait_object::Config;
+use serde_trait_object_demo::patch::{PatchError, PatchOptions};
+
+#[test]
+fn merge_patch_updates_entries() {
+    let mut cfg = Config::from_str().unwrap();
+    cfg.apply_merge_patch(&json!({
+        "provider": "openai-personal",
+        "provider_config": {
+            "openai-personal": { "model": "gpt-4" },
+            "Claude2": { "cookies": { "cookie2": null } },
+        },
+    }), PatchOptions::default()).unwrap();
+
+    assert_eq!(cfg.provider(), "openai-personal");
+    let personal = cfg.current_provider_config().unwrap();
+    assert_eq!(personal.model().as_deref(), Some("gpt-4"));
+    // still inherits from its base
+    assert_eq!(personal.timeout(), Some(std::time::Duration::from_secs(30)));
+    assert_eq!(cfg.provider_config.extends("openai-personal"), Some("OpenaiMobile"));
+    let cookies = cfg.provider_config.get("Claude2").unwrap().cookies().unwrap();
+    assert_eq!(cookies.keys().collect::<Vec<_>>(), ["cookie1"]);
+}
+
+#[test]
+fn json_patch_adds_and_replaces() {
+    let mut cfg = Config::from_str().unwrap();
+    cfg.apply_json_patch(&json!([
+        { "op": "replace", "path": "/provider_config/OpenaiMobile/token", "value": "tk-new" },
+        { "op": "add", "path": "/provider_config/claude-work", "value": { "provider": "Claude2", "cookies": { "s": "x" } } },
+        { "op": "add", "path": "/fallback/-", "value": "claude-work" },
+    ]), PatchOptions::default()).unwrap();
+
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "tk-new");
+    assert_eq!(cfg.provider_config.get("claude-work").unwrap().kind(), "Claude2");
+    assert_eq!(cfg.providers(), ["OpenaiMobile", "Claude2", "claude-work"]);
+}
+
+#[test]
+fn kind_change_needs_permission() {
+    let mut cfg = Config::from_str().unwrap();
+    let patch = json!([
+        { "op": "replace", "path": "/provider_config/openai-personal",
+          "value": { "provider": "Claude2", "cookies": { "s": "x" } } },
+    ]);
+
+    let err = cfg.apply_json_patch(&patch, PatchOptions::default()).err().unwrap();
+    assert!(matches!(err, PatchError::KindChange { ref provider, .. } if provider == "openai-personal"), "{}", err);
+    assert_eq!(cfg.provider_config.get("openai-personal").unwrap().kind(), "OpenaiMobile");
+
+    cfg.apply_json_patch(&patch, PatchOptions { allow_kind_change: true }).unwrap();
+    assert_eq!(cfg.provider_config.get("openai-personal").unwrap().kind(), "Claude2");
+}
+
+#[test]
+fn invalid_results_are_rejected() {
+    let mut cfg = Config::from_str().unwrap();
+    let err = cfg.apply_merge_patch(&json!({ "provider_config": { "OpenaiMobile": { "token": "" } } }), PatchOptions::default())
+        .err().unwrap();
+    assert!(matches!(err, PatchError::Invalid(_)), "{}", err);
+    assert_eq!(err.to_string(), "patched config is invalid: provider config OpenaiMobile: token is empty");
+
+    let err = cfg.apply_json_patch(&json!([{ "op": "remove", "path": "/provider_config/missing" }]), PatchOptions::default())
+        .err().unwrap();
+    assert!(matches!(err, PatchError::Patch(_)), "{}", err);
+
+    let err = cfg.apply_merge_patch(&json!({ "provider_config": { "Claude2": { "provider": "OpenaiMobile" } } }), PatchOptions { allow_kind_change: true })
+        .err().unwrap();
+    assert!(matches!(err, PatchError::Config(_)), "{}", err);
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "tk-xxxxxxxx-001");
+}
+
+#[test]
+fn patch_values_are_not_interpolated() {
+    std::env::set_var("PROBE_TOKEN", "tk-from-env");
+    let mut cfg = Config::from_str().unwrap();
+    cfg.apply_merge_patch(&json!({
+        "provider_config": { "OpenaiMobile": { "token": "${PROBE_TOKEN}" } },
+    }), PatchOptions::default()).unwrap();
+    cfg.apply_json_patch(&json!([
+        { "op": "add", "path": "/provider_config/Claude2/cookies/stolen", "value": "${provider_config.OpenaiMobile.token}" },
+    ]), PatchOptions::default()).unwrap();
+
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "${PROBE_TOKEN}");
+    let cookies = cfg.provider_config.get("Claude2").unwrap().cookies().unwrap();
+    assert_eq!(cookies["stolen"], "${provider_config.OpenaiMobile.token}");
+    // the templates of the loaded document still expand
+    assert_eq!(cfg.provider_config.get("openai-personal").unwrap().api_base(), "https://api.openai.com/v1");
+}
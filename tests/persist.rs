@@ -0,0 +1,68 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use serde_trait_object_demo::config_trait_object::Config;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("config-persist-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn save_creates_private_file_and_reloads() {
+    let dir = temp_dir("create");
+    let path = dir.join("config.yaml");
+    let mut cfg = Config::from_str().unwrap();
+    cfg.save(&path).unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let mut reloaded = Config::load(&path).unwrap();
+    assert_eq!(reloaded.source.as_ref().unwrap().path(), path);
+    assert_eq!(reloaded.provider_config.get("openai-personal").unwrap().token(), "tk-xxxxxxxx-002");
+
+    // saving again over the file it was loaded from is fine, and leaves no temp file behind
+    reloaded.provider = Some("Claude2".to_string().into());
+    reloaded.save(&path).unwrap();
+    assert_eq!(Config::load(&path).unwrap().provider(), "Claude2");
+    let mut names: Vec<String> = std::fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["config.yaml", "config.yaml.lock"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn refuses_to_overwrite_changed_file() {
+    let dir = temp_dir("changed");
+    let path = dir.join("config.yaml");
+    Config::from_str().unwrap().save(&path).unwrap();
+
+    let mut first = Config::load(&path).unwrap();
+    let mut second = Config::load(&path).unwrap();
+    first.provider = Some("Claude2".to_string().into());
+    first.save(&path).unwrap();
+    second.fallback.clear();
+    let err = second.save(&path).err().unwrap();
+    assert!(err.to_string().contains("changed since it was loaded"), "{}", err);
+
+    // the same file edited by hand
+    std::fs::write(&path, "provider: Claude2\nprovider_config: {}\n").unwrap();
+    assert!(first.save(&path).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "provider: Claude2\nprovider_config: {}\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn refuses_to_overwrite_unrelated_file() {
+    let dir = temp_dir("unrelated");
+    let path = dir.join("config.yaml");
+    std::fs::write(&path, "not a config").unwrap();
+
+    let err = Config::from_str().unwrap().save(&path).err().unwrap();
+    assert!(err.to_string().contains("was not loaded into this config"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a config");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
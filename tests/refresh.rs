@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde_trait_object_demo::config_trait_object::{Config, ProviderConfig};
+use serde_trait_object_demo::refresh::{Credential, Refresher};
+use serde_trait_object_demo::shared::SharedConfig;
+
+fn config(expires_at: DateTime<Utc>) -> Config {
+    serde_yaml::from_str(&format!(r#"
+provider: OpenaiMobile
+provider_config:
+  OpenaiMobile:
+    provider: OpenaiMobile
+    token: tk-old
+    cookies: {{}}
+    expires_at: {}
+  Claude2:
+    provider: Claude2
+    cookies:
+      session: abc
+"#, expires_at.to_rfc3339())).unwrap()
+}
+
+#[test]
+fn expiry_is_read_and_checked() {
+    let in_two_minutes = Utc::now() + chrono::Duration::minutes(2);
+    let cfg = config(in_two_minutes);
+    let pcfg = cfg.provider_config.get("OpenaiMobile").unwrap();
+
+    assert_eq!(pcfg.expires_at().unwrap().timestamp(), in_two_minutes.timestamp());
+    assert!(!pcfg.is_expired());
+    assert!(pcfg.expires_within(Duration::from_secs(5 * 60)));
+    assert!(!pcfg.expires_within(Duration::from_secs(60)));
+
+    // no expiry is never expired
+    let claude = cfg.provider_config.get("Claude2").unwrap();
+    assert!(!claude.is_expired());
+    assert!(!claude.expires_within(Duration::MAX));
+
+    assert!(config(Utc::now() - chrono::Duration::seconds(1)).provider_config.get("OpenaiMobile").unwrap().is_expired());
+    let reloaded: Config = serde_yaml::from_str(&cfg.to_string().unwrap()).unwrap();
+    assert_eq!(reloaded.provider_config.get("OpenaiMobile").unwrap().expires_at(), pcfg.expires_at());
+}
+
+#[tokio::test]
+async fn due_credentials_are_refreshed_once() {
+    let shared = SharedConfig::new(config(Utc::now() + chrono::Duration::minutes(2)));
+    let next = Utc::now() + chrono::Duration::hours(1);
+    let refresher = Refresher::new(shared.clone(), move |name: String, pcfg: Box<dyn ProviderConfig>| async move {
+        assert_eq!(name, "OpenaiMobile");
+        assert_eq!(pcfg.token(), "tk-old");
+        Ok(Credential { token: Some("tk-new".to_string()), expires_at: Some(next), ..Credential::default() })
+    });
+
+    assert_eq!(refresher.refresh_due().await, ["OpenaiMobile"]);
+    let pcfg = shared.current_provider_config().unwrap();
+    assert_eq!(pcfg.token(), "tk-new");
+    assert_eq!(pcfg.expires_at(), Some(next));
+    assert!(refresher.refresh_due().await.is_empty());
+}
+
+#[tokio::test]
+async fn background_task_refreshes_before_expiry_and_retries_failures() {
+    let shared = SharedConfig::new(config(Utc::now() + chrono::Duration::seconds(30)));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let hook_calls = calls.clone();
+    // before spawning, so the refresh can't be published before the test waits for it
+    let mut changes = shared.subscribe();
+    let task = Refresher::new(shared.clone(), move |_: String, _: Box<dyn ProviderConfig>| {
+        let call = hook_calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            anyhow::ensure!(call > 0, "token endpoint unavailable");
+            Ok(Credential { token: Some("tk-new".to_string()), expires_at: Some(Utc::now() + chrono::Duration::hours(1)), ..Credential::default() })
+        }
+    }).with_interval(Duration::from_millis(10)).spawn();
+
+    tokio::time::timeout(Duration::from_secs(5), changes.changed()).await.unwrap().unwrap();
+    task.abort();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(shared.current_provider_config().unwrap().token(), "tk-new");
+}
+
+fn claude(expires_at: DateTime<Utc>) -> Config {
+    serde_yaml::from_str(&format!(r#"
+provider: Claude2
+provider_config:
+  Claude2:
+    provider: Claude2
+    cookies:
+      session: old
+    expires_at: {}
+"#, expires_at.to_rfc3339())).unwrap()
+}
+
+#[tokio::test]
+async fn cookies_are_refreshed() {
+    let shared = SharedConfig::new(claude(Utc::now() + chrono::Duration::minutes(2)));
+    let next = Utc::now() + chrono::Duration::hours(1);
+    let cookies = HashMap::from([("session".to_string(), "new".to_string())]);
+    let refreshed = cookies.clone();
+    let refresher = Refresher::new(shared.clone(), move |_: String, _: Box<dyn ProviderConfig>| {
+        let cookies = refreshed.clone();
+        async move { Ok(Credential { cookies: Some(cookies), expires_at: Some(next), ..Credential::default() }) }
+    });
+
+    assert_eq!(refresher.refresh_due().await, ["Claude2"]);
+    let pcfg = shared.current_provider_config().unwrap();
+    assert_eq!(pcfg.cookies(), Some(cookies));
+    assert_eq!(pcfg.expires_at(), Some(next));
+}
+
+#[tokio::test]
+async fn expiry_is_kept_when_the_credential_cannot_be_stored() {
+    let expires_at = Utc::now() + chrono::Duration::minutes(2);
+    let shared = SharedConfig::new(claude(expires_at));
+    let refresher = Refresher::new(shared.clone(), |_: String, _: Box<dyn ProviderConfig>| async {
+        Ok(Credential { token: Some("tk-new".to_string()), expires_at: Some(Utc::now() + chrono::Duration::hours(1)), ..Credential::default() })
+    });
+
+    assert!(refresher.refresh_due().await.is_empty());
+    let pcfg = shared.current_provider_config().unwrap();
+    assert_eq!(pcfg.expires_at().unwrap().timestamp(), expires_at.timestamp());
+    assert_eq!(pcfg.cookies().unwrap()["session"], "old");
+}
@@ -0,0 +1,76 @@
+use serde_trait_object_demo::config_trait_object::{Config, OpenaiMobile};
+use serde_trait_object_demo::shared::SharedConfig;
+
+fn set_token(shared: &SharedConfig, provider: &str, token: &str) -> anyhow::Result<()> {
+    shared.update(|cfg| {
+        let mut pcfg = dyn_clone::clone_box(cfg.provider_config.get(provider)
+            .ok_or_else(|| anyhow::format_err!("no provider config {}", provider))?);
+        pcfg.set_token(token.to_string());
+        cfg.provider_config.set(provider, pcfg);
+        Ok(())
+    })
+}
+
+#[test]
+fn is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedConfig>();
+}
+
+#[test]
+fn snapshots_do_not_see_later_updates() {
+    let shared = SharedConfig::new(Config::from_str().unwrap());
+    let before = shared.current_provider_config().unwrap();
+    set_token(&shared, "OpenaiMobile", "new_token").unwrap();
+
+    assert_eq!(before.name(), "OpenaiMobile");
+    assert_eq!(before.token(), "tk-xxxxxxxx-001");
+    assert_eq!(shared.current_provider_config().unwrap().token(), "new_token");
+}
+
+#[test]
+fn failed_update_is_not_published() {
+    let shared = SharedConfig::new(Config::from_str().unwrap());
+    let changes = shared.subscribe();
+    let err = shared.update::<()>(|cfg| {
+        cfg.provider = Some("Claude2".to_string().into());
+        anyhow::bail!("rejected")
+    });
+
+    assert!(err.is_err());
+    assert_eq!(shared.snapshot().provider(), "OpenaiMobile");
+    assert!(!changes.has_changed().unwrap());
+}
+
+#[test]
+fn concurrent_writers_are_serialized() {
+    let shared = SharedConfig::new(Config::from_str().unwrap());
+    std::thread::scope(|scope| {
+        for i in 0..8 {
+            let shared = shared.clone();
+            scope.spawn(move || {
+                shared.update(|cfg| {
+                    let pcfg = OpenaiMobile { token: Some(format!("tk-{}", i)), ..Default::default() };
+                    cfg.provider_config.set(&format!("openai-{}", i), Box::new(pcfg));
+                    Ok(())
+                }).unwrap();
+            });
+        }
+    });
+
+    let snapshot = shared.snapshot();
+    for i in 0..8 {
+        assert_eq!(snapshot.provider_config.get(&format!("openai-{}", i)).unwrap().token(), format!("tk-{}", i));
+    }
+}
+
+#[tokio::test]
+async fn subscribers_get_new_snapshots() {
+    let shared = SharedConfig::new(Config::from_str().unwrap());
+    let mut changes = shared.subscribe();
+    let writer = shared.clone();
+    tokio::spawn(async move { set_token(&writer, "Claude2", "unused").unwrap() });
+
+    changes.changed().await.unwrap();
+    assert!(changes.borrow_and_update().provider_config.get("Claude2").is_some());
+}
@@ -0,0 +1,72 @@
+use serde_trait_object_demo::config_trait_object::{Claude2, Config};
+use serde_trait_object_demo::shared::SharedConfig;
+use serde_trait_object_demo::transaction::{TransactionError, ValidationError};
+
+#[test]
+fn commit_switches_provider_and_token_together() {
+    let mut cfg = Config::from_str().unwrap();
+    cfg.transaction(|tx| {
+        tx.set_active("openai-personal");
+        tx.set_token("openai-personal", "new_token")
+    }).unwrap();
+
+    assert_eq!(cfg.provider(), "openai-personal");
+    assert_eq!(cfg.current_provider_config().unwrap().token(), "new_token");
+}
+
+#[test]
+fn invalid_result_rolls_back() {
+    let mut cfg = Config::from_str().unwrap();
+    let err = cfg.transaction(|tx| {
+        tx.set_active("Claude2");
+        tx.set("Claude2", Box::new(Claude2::default()));
+        tx.remove("openai-personal");
+        tx.set_fallback(vec!["openai-personal".to_string()]);
+        Ok(())
+    }).err().unwrap();
+
+    let TransactionError::Invalid(errors) = &err else {
+        panic!("unexpected error {}", err);
+    };
+    assert_eq!(errors, &[
+        ValidationError { provider: "Claude2".to_string(), message: "cookies is empty".to_string() },
+        ValidationError { provider: "openai-personal".to_string(), message: "selected but missing".to_string() },
+    ]);
+    assert_eq!(cfg.provider(), "OpenaiMobile");
+    assert!(cfg.provider_config.get("openai-personal").is_some());
+    assert!(!cfg.provider_config.get("Claude2").unwrap().cookies().unwrap().is_empty());
+}
+
+#[test]
+fn aborted_closure_rolls_back() {
+    let mut cfg = Config::from_str().unwrap();
+    let err = cfg.transaction(|tx| {
+        tx.set_active("Claude2");
+        tx.set_token("missing", "x")
+    }).err().unwrap();
+
+    assert!(matches!(err, TransactionError::Aborted(_)));
+    assert_eq!(err.to_string(), "transaction aborted: no provider config missing");
+    assert_eq!(cfg.provider(), "OpenaiMobile");
+}
+
+#[test]
+fn shared_config_notifies_once_per_commit() {
+    let shared = SharedConfig::new(Config::from_str().unwrap());
+    let mut changes = shared.subscribe();
+
+    shared.transaction(|tx| {
+        tx.set_active("openai-personal");
+        tx.set_token("openai-personal", "new_token")
+    }).unwrap();
+    assert!(changes.has_changed().unwrap());
+    assert_eq!(changes.borrow_and_update().provider(), "openai-personal");
+
+    let err = shared.transaction(|tx| {
+        tx.set_active("missing");
+        Ok(())
+    });
+    assert!(matches!(err, Err(TransactionError::Invalid(_))));
+    assert!(!changes.has_changed().unwrap());
+    assert_eq!(shared.snapshot().provider(), "openai-personal");
+}